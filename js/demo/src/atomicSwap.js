@@ -14,9 +14,9 @@ function prettyPrintSignedOffer(signedOffer) {
     Signature: ${signedOffer.signature} \n
     Signer: ${signedOffer.signer} \n
     Offer: \n
-        offer_token: ${signedOffer.offer.offer_token} \n
+        offer_asset: ${signedOffer.offer.offer_asset} \n
         offer_amount: ${signedOffer.offer.offer_amount} \n
-        requested_token: ${signedOffer.offer.requested_token} \n
+        requested_asset: ${signedOffer.offer.requested_asset} \n
         requested_amount: ${signedOffer.offer.requested_amount} \n
         nonce: ${signedOffer.offer.nonce}
         `);
@@ -127,16 +127,21 @@ describe('prc20', () => {
         expect(aliceBalanceAliceTokenInit.sub(aliceBalanceAliceToken).toNumber()).toBe(0);
         expect(aliceBalanceBobToken.sub(aliceBalanceBobTokenInit).toNumber()).toBe(0);
     });
-    test('swap fails wrong nonce', async () => {
+    test('swap fails expired offer', async () => {
         const aliceBalanceAliceTokenInit = await parrot.getTokenBalance(ALICE.address, tokenIdAlice);
         const aliceBalanceBobTokenInit = await parrot.getTokenBalance(ALICE.address, tokenIdBob);
         const bobBalanceAliceTokenInit = await parrot.getTokenBalance(BOB.address, tokenIdAlice);
         const bobBalanceBobTokenInit = await parrot.getTokenBalance(BOB.address, tokenIdBob);
-        // Creeate a wrong offer type with wrong nonce
-        let senderNonce = await parrot.getNonce(BOB.address);
-        senderNonce += 2;
+        // Create an offer that has already expired
         const offer = await parrot.api.createType('Offer', {
-            offer_token: tokenIdBob, offer_amount: AMOUNT, requested_token: tokenIdAlice, requested_amount: AMOUNT, nonce: senderNonce,
+            offer_asset: { Token: tokenIdBob },
+            offer_amount: AMOUNT,
+            requested_asset: { Token: tokenIdAlice },
+            requested_amount: AMOUNT,
+            nonce: 0,
+            partial_fill: false,
+            expiry: 0,
+            taker: null,
         });
         // Bob creates a signature for the offer
         const signature = await parrot.signOffer(BOB, offer);
@@ -3,10 +3,12 @@ const { BN } = require('bn.js');
 const Util = require('@polkadot/util');
 const UtilCrypto = require('@polkadot/util-crypto');
 const ADDITIONAL_TYPES = require('../types/types.json');
+const ADDITIONAL_RPC = require('../types/rpc.json');
 
 class ParrotInterface {
     constructor(providerUrl='ws://localhost:9944') {
         this.types = ADDITIONAL_TYPES;
+        this.rpc = ADDITIONAL_RPC;
         this.api = undefined;
         this.util = Util;
         this.utilCrypto = UtilCrypto;
@@ -20,7 +22,7 @@ class ParrotInterface {
     async initApi() {
         const ws = new WsProvider(this.providerUrl);
         // Instantiate the API
-        this.api = await ApiPromise.create({ types: this.types, provider: ws });
+        this.api = await ApiPromise.create({ types: this.types, rpc: this.rpc, provider: ws });
         // Retrieve the chain & node information information via rpc calls
         const [chain, nodeName, nodeVersion] = await Promise.all([
             this.api.rpc.system.chain(),
@@ -52,6 +54,17 @@ class ParrotInterface {
         return bal.data.free;
     }
 
+    // the bytes signed offline for a message: the message tag,
+    // the genesis hash and spec name of the chain, then the message itself
+    signingPayload(tag, message) {
+        return Util.u8aConcat(
+            Util.compactAddLength(Util.stringToU8a(tag)),
+            this.api.genesisHash.toU8a(),
+            this.api.runtimeVersion.specName.toU8a(),
+            message.toU8a(),
+        );
+    }
+
     async getNonce(address) {
         const stats = await this.api.query.system.account(address);
         return stats.nonce;
@@ -72,10 +85,10 @@ class ParrotInterface {
 
     // Creates a PRC20 Token, returns tokenID
     // TODO: should be improved to use event to see if token is successfully created
-    async createToken(keyringPair, totalSupply) {
+    async createToken(keyringPair, totalSupply, metadata = { name: '', symbol: '', decimals: 0, uri: null }, minBalance = 0) {
         const tokenCount = await this.api.query.prc20.tokenCount();
         // console.log(`Current token count ${tokenCount}`);
-        const tx = this.api.tx.prc20.createToken(totalSupply);
+        const tx = this.api.tx.prc20.createToken(totalSupply, minBalance, metadata);
         // Sign and send the transaction using keyring
         const hash = await tx.signAndSend(keyringPair);
         console.log('CreateToken sent with hash', hash.toHex());
@@ -83,6 +96,80 @@ class ParrotInterface {
         return tokenCount;
     }
 
+    // sets the metadata of a token, only works for the token owner
+    async setTokenMetadata(keyringPair, tokenId, metadata) {
+        const tx = this.api.tx.prc20.setMetadata(tokenId, metadata);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`SetMetadata sent with hash ${hash}`);
+    }
+
+    // returns the metadata of a token, null if the token does not exist
+    async getTokenMetadata(tokenId) {
+        const metadata = await this.api.rpc.prc20.tokenMetadata(tokenId);
+        return metadata.isSome ? metadata.unwrap() : null;
+    }
+
+    // returns the total supply of a token
+    async getTotalSupply(tokenId) {
+        const supply = await this.api.rpc.prc20.totalSupply(tokenId);
+        return supply;
+    }
+
+    // returns the number of tokens created
+    async getTokenCount() {
+        const count = await this.api.rpc.prc20.tokenCount();
+        return count;
+    }
+
+    // destroys a token once its supply is burned, returning the creation deposit
+    async destroyToken(keyringPair, tokenId) {
+        const tx = this.api.tx.prc20.destroyToken(tokenId);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`DestroyToken sent with hash ${hash}`);
+    }
+
+    // mints new tokens to an account, only works for the token owner
+    async mintToken(keyringPair, to, tokenId, amount) {
+        const tx = this.api.tx.prc20.mint(to, tokenId, amount);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Mint sent with hash ${hash}`);
+    }
+
+    // burns tokens from the owner's balance, only works for the token owner
+    async burnToken(keyringPair, tokenId, amount) {
+        const tx = this.api.tx.prc20.burn(tokenId, amount);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Burn sent with hash ${hash}`);
+    }
+
+    // freezes an account for a token, only works for the token owner
+    async freezeAccount(keyringPair, tokenId, who) {
+        const tx = this.api.tx.prc20.freeze(tokenId, who);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Freeze sent with hash ${hash}`);
+    }
+
+    // thaws a frozen account for a token, only works for the token owner
+    async thawAccount(keyringPair, tokenId, who) {
+        const tx = this.api.tx.prc20.thaw(tokenId, who);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Thaw sent with hash ${hash}`);
+    }
+
+    // pauses all transfers of a token, only works for the token owner
+    async pauseToken(keyringPair, tokenId) {
+        const tx = this.api.tx.prc20.pause(tokenId);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Pause sent with hash ${hash}`);
+    }
+
+    // resumes transfers of a paused token, only works for the token owner
+    async unpauseToken(keyringPair, tokenId) {
+        const tx = this.api.tx.prc20.unpause(tokenId);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log(`Unpause sent with hash ${hash}`);
+    }
+
     // Transfer a token
     async transferToken(keyringPair, to, tokenId, amount) {
         const prc20TransferTx = await this.api.tx.prc20.transfer(to, tokenId, amount);
@@ -90,16 +177,38 @@ class ParrotInterface {
         console.log('PRC20 Transfer sent with hash', hash.toHex());
     }
 
+    // Transfer a token, failing instead of reaping the sender below the minimum balance
+    async transferTokenKeepAlive(keyringPair, to, tokenId, amount) {
+        const tx = this.api.tx.prc20.transferKeepAlive(to, tokenId, amount);
+        const hash = await tx.signAndSend(keyringPair);
+        console.log('PRC20 TransferKeepAlive sent with hash', hash.toHex());
+    }
+
     // approves token
-    async approveToken(keyingPair, who, tokenId, amount) {
-        const approveTx = await this.api.tx.prc20.approve(who, tokenId, amount);
+    async approveToken(keyingPair, who, tokenId, amount, expiry = null) {
+        const approveTx = await this.api.tx.prc20.approve(who, tokenId, amount, expiry);
         const hash = await approveTx.signAndSend(keyingPair);
         console.log(`Approve sent with hash ${hash}`);
     }
 
-    // return the allowance of an account, for a given token and address 
+    // increases the allowance of an account
+    async increaseAllowance(keyingPair, who, tokenId, amount) {
+        const tx = this.api.tx.prc20.increaseAllowance(who, tokenId, amount);
+        const hash = await tx.signAndSend(keyingPair);
+        console.log(`IncreaseAllowance sent with hash ${hash}`);
+    }
+
+    // decreases the allowance of an account
+    async decreaseAllowance(keyingPair, who, tokenId, amount) {
+        const tx = this.api.tx.prc20.decreaseAllowance(who, tokenId, amount);
+        const hash = await tx.signAndSend(keyingPair);
+        console.log(`DecreaseAllowance sent with hash ${hash}`);
+    }
+
+    // return the allowance of an account, for a given token and address
+    // (zero once the allowance has expired)
     async getAllowanceOf(wallet, who, tokenId) {
-        const bal = await this.api.query.prc20.allowance([tokenId, wallet, who]);
+        const bal = await this.api.rpc.prc20.allowanceOf(tokenId, wallet, who);
         return bal;
     }
 
@@ -112,21 +221,77 @@ class ParrotInterface {
 
     // returns the token balance
     async getTokenBalance(address, tokenId) {
-        const bal = await this.api.query.prc20.balances([tokenId, address]);
+        const bal = await this.api.rpc.prc20.balanceOf(tokenId, address);
         // console.log(`Token ${tokenId} Wallet: ${address} Balance: ${bal}`)
         return bal;
     }
 
-    // creates an offer struct
-    async createOffer(address, offerToken, offerAmount, requestedToken, requestedAmount) {
-        const senderNonce = await this.getNonce(address);
+    // returns a page of the [tokenId, balance] pairs of the tokens held by an account
+    async getTokensOf(address, start = 0, limit = 100) {
+        const tokens = await this.api.rpc.prc20.tokensOf(address, start, limit);
+        return tokens;
+    }
+
+    // returns the number of accounts holding a token
+    async getHolderCount(tokenId) {
+        const count = await this.api.rpc.prc20.holderCount(tokenId);
+        return count;
+    }
+
+    // returns the [address, balance] pairs of the largest holders of a token
+    async getTopHolders(tokenId, count = 10) {
+        const holders = await this.api.rpc.prc20.topHolders(tokenId, count);
+        return holders;
+    }
+
+    // takes a snapshot of the balances of a token, only callable by its owner
+    async snapshot(keyRingPair, tokenId) {
+        const tx = this.api.tx.prc20.snapshot(tokenId);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('Snapshot sent with hash', hash.toHex());
+    }
+
+    // returns the id of the last snapshot taken of a token, 0 if none
+    async getCurrentSnapshot(tokenId) {
+        const snapshotId = await this.api.rpc.prc20.currentSnapshot(tokenId);
+        return snapshotId;
+    }
+
+    // returns the balance of an account at a snapshot, null if it was not taken
+    async getBalanceOfAt(address, tokenId, snapshotId) {
+        const bal = await this.api.rpc.prc20.balanceOfAt(tokenId, address, snapshotId);
+        return bal.isSome ? bal.unwrap() : null;
+    }
+
+    // returns the total supply of a token at a snapshot, null if it was not taken
+    async getTotalSupplyAt(tokenId, snapshotId) {
+        const supply = await this.api.rpc.prc20.totalSupplyAt(tokenId, snapshotId);
+        return supply.isSome ? supply.unwrap() : null;
+    }
+
+    // creates an offer struct, valid for validFor blocks
+    // (the nonce only tells apart otherwise identical offers)
+    // assets are either a tokenId or 'Native' for the native currency,
+    // if a taker address is given only the taker can swap the offer,
+    // a relayerFee { asset, amount } is paid by the maker to the submitter
+    async createOffer(address, offerAsset, offerAmount, requestedAsset, requestedAmount, partialFill = false, validFor = 600, taker = null, relayerFee = null) {
+        const senderNonce = Date.now();
+        const header = await this.api.rpc.chain.getHeader();
+        const expiry = header.number.toNumber() + validFor;
         const offer = await this.api.createType(
             'Offer', {
-            offer_token: offerToken,
+            offer_asset: offerAsset === 'Native' ? offerAsset : { Token: offerAsset },
             offer_amount: offerAmount,
-            requested_token: requestedToken,
+            requested_asset: requestedAsset === 'Native' ? requestedAsset : { Token: requestedAsset },
             requested_amount: requestedAmount,
             nonce: senderNonce,
+            partial_fill: partialFill,
+            expiry,
+            taker,
+            relayer_fee: relayerFee && {
+                asset: relayerFee.asset === 'Native' ? relayerFee.asset : { Token: relayerFee.asset },
+                amount: relayerFee.amount,
+            },
         },
         );
         return offer;
@@ -134,7 +299,7 @@ class ParrotInterface {
 
     // takes an offer and returns a signature
     async signOffer(keyRingPair, offer) {
-        const encodedOffer = offer.toU8a();
+        const encodedOffer = this.signingPayload('prc20/offer', offer);
         const signature = keyRingPair.sign(encodedOffer, { withType: true });
         return signature;
     }
@@ -152,6 +317,213 @@ class ParrotInterface {
         console.log('Swap sent by Alice with hash', hash.toHex());
     }
 
+    // creates a permit struct, letting spender use value tokens until the deadline block
+    async createPermit(address, tokenId, spender, value, deadline) {
+        const nonce = await this.api.query.prc20.permitNonces(address);
+        const permit = await this.api.createType('Permit', {
+            token: tokenId, spender, value, nonce, deadline,
+        });
+        return permit;
+    }
+
+    // takes a permit and returns a signature
+    async signPermit(keyRingPair, permit) {
+        const signature = keyRingPair.sign(this.signingPayload('prc20/permit', permit), { withType: true });
+        return signature;
+    }
+
+    // creates signed permit struct
+    async createSignedPermit(permit, signature, signer) {
+        const signedPermit = await this.api.createType('SignedPermit', { permit, signature, signer });
+        return signedPermit;
+    }
+
+    // submits a signed permit on behalf of its signer
+    async permit(keyRingPair, signedPermit) {
+        const tx = this.api.tx.prc20.permit(signedPermit);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log(`Permit sent with hash ${hash}`);
+    }
+
+    // fills part of a partially fillable signed offer
+    async swapPartial(keyRingPair, signedOffer, fillAmount) {
+        const swapTx = this.api.tx.prc20.swapPartial(signedOffer, fillAmount);
+        const hash = await swapTx.signAndSend(keyRingPair);
+        console.log('SwapPartial sent with hash', hash.toHex());
+    }
+
+    // settles many signed offers in one tx, settlements is a list of
+    // [signedOffer, fillAmount] (fillAmount null to fill the whole offer)
+    async settleBatch(keyRingPair, settlements, allOrNothing = false) {
+        const tx = this.api.tx.prc20.settleBatch(settlements, allOrNothing);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('SettleBatch sent with hash', hash.toHex());
+    }
+
+    // creates a ring trade struct from { from, to, asset, amount } legs,
+    // valid for validFor blocks, assets are either a tokenId or 'Native'
+    async createRingTrade(legs, validFor = 600) {
+        const header = await this.api.rpc.chain.getHeader();
+        const trade = await this.api.createType('RingTrade', {
+            legs: legs.map((leg) => ({
+                ...leg,
+                asset: leg.asset === 'Native' ? leg.asset : { Token: leg.asset },
+            })),
+            nonce: Date.now(),
+            expiry: header.number.toNumber() + validFor,
+        });
+        return trade;
+    }
+
+    // takes a ring trade and returns the signature of one of its parties
+    async signRingTrade(keyRingPair, trade) {
+        const signature = keyRingPair.sign(this.signingPayload('prc20/ring', trade), { withType: true });
+        return signature;
+    }
+
+    // settles a ring trade, signatures are in the order of the legs
+    async swapRing(keyRingPair, trade, signatures) {
+        const signedTrade = await this.api.createType('SignedRingTrade', { trade, signatures });
+        const tx = this.api.tx.prc20.swapRing(signedTrade);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('SwapRing sent with hash', hash.toHex());
+    }
+
+    // cancels an offer previously created by the keyRingPair
+    async cancelOffer(keyRingPair, offer) {
+        const cancelTx = this.api.tx.prc20.cancelOffer(offer);
+        const hash = await cancelTx.signAndSend(keyRingPair);
+        console.log('CancelOffer sent with hash', hash.toHex());
+    }
+
+    // places an order on the order book, the offered tokens are held in escrow
+    async placeOrder(keyRingPair, offerToken, offerAmount, requestedToken, requestedAmount) {
+        const tx = this.api.tx.prc20.placeOrder(offerToken, offerAmount, requestedToken, requestedAmount);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('PlaceOrder sent with hash', hash.toHex());
+    }
+
+    // fills fillAmount of the offered tokens of an order
+    async fillOrder(keyRingPair, orderId, fillAmount) {
+        const tx = this.api.tx.prc20.fillOrder(orderId, fillAmount);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('FillOrder sent with hash', hash.toHex());
+    }
+
+    // cancels an order, returning the escrowed tokens
+    async cancelOrder(keyRingPair, orderId) {
+        const tx = this.api.tx.prc20.cancelOrder(orderId);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('CancelOrder sent with hash', hash.toHex());
+    }
+
+    // returns an order of the order book
+    async getOrder(orderId) {
+        const order = await this.api.query.prc20.orders(orderId);
+        return order;
+    }
+
+    // returns the orders offering offerToken for requestedToken,
+    // as a list of price levels (lowest first) with their order ids (oldest first),
+    // the storage keys of the book end with the price (u128) or the order id (u64)
+    async getOrderBook(offerToken, requestedToken) {
+        const keyTail = (key, type, length) => this.api.createType(type, key.toU8a(true).slice(-length));
+        const pair = [offerToken, requestedToken];
+        const prices = (await this.api.query.prc20.priceLevels.entries(pair))
+            .map(([key]) => keyTail(key, 'u128', 16))
+            .sort((a, b) => a.cmp(b));
+        const levels = await Promise.all(prices.map(async (price) => {
+            const orderIds = (await this.api.query.prc20.orderBook.entries([offerToken, requestedToken, price]))
+                .map(([key]) => keyTail(key, 'u64', 8))
+                .sort((a, b) => a.cmp(b));
+            return { price, orderIds };
+        }));
+        return levels;
+    }
+
+    // locks an asset (a tokenId or 'Native') for recipient until the timeout
+    // block, hashlock is the sha256 hash of a secret preimage
+    async lockHtlc(keyRingPair, recipient, asset, amount, hashlock, timeout) {
+        const tx = this.api.tx.prc20.lockHtlc(recipient, asset === 'Native' ? asset : { Token: asset },
+            amount, hashlock, timeout);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('LockHtlc sent with hash', hash.toHex());
+    }
+
+    // claims a htlc for its recipient by revealing the preimage
+    async claimHtlc(keyRingPair, htlcId, preimage) {
+        const tx = this.api.tx.prc20.claimHtlc(htlcId, preimage);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('ClaimHtlc sent with hash', hash.toHex());
+    }
+
+    // refunds an expired htlc to its sender
+    async refundHtlc(keyRingPair, htlcId) {
+        const tx = this.api.tx.prc20.refundHtlc(htlcId);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('RefundHtlc sent with hash', hash.toHex());
+    }
+
+    // returns a pending htlc
+    async getHtlc(htlcId) {
+        const htlc = await this.api.query.prc20.htlcs(htlcId);
+        return htlc;
+    }
+
+    // creates the amm pool of a pair of tokens
+    async createPool(keyRingPair, tokenA, tokenB) {
+        const tx = this.api.tx.amm.createPool(tokenA, tokenB);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('CreatePool sent with hash', hash.toHex());
+    }
+
+    // adds liquidity to an amm pool, for at least minShares LP shares
+    async addLiquidity(keyRingPair, tokenA, tokenB, amountA, amountB, minShares = 0) {
+        const tx = this.api.tx.amm.addLiquidity(tokenA, tokenB, amountA, amountB, minShares);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('AddLiquidity sent with hash', hash.toHex());
+    }
+
+    // removes liquidity from an amm pool, for at least minA and minB tokens
+    async removeLiquidity(keyRingPair, tokenA, tokenB, shares, minA = 0, minB = 0) {
+        const tx = this.api.tx.amm.removeLiquidity(tokenA, tokenB, shares, minA, minB);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('RemoveLiquidity sent with hash', hash.toHex());
+    }
+
+    // swaps exactly amountIn of tokenIn for at least minAmountOut of tokenOut
+    async swapExactIn(keyRingPair, tokenIn, tokenOut, amountIn, minAmountOut) {
+        const tx = this.api.tx.amm.swapExactIn(tokenIn, tokenOut, amountIn, minAmountOut);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('SwapExactIn sent with hash', hash.toHex());
+    }
+
+    // swaps at most maxAmountIn of tokenIn for exactly amountOut of tokenOut
+    async swapExactOut(keyRingPair, tokenIn, tokenOut, amountOut, maxAmountIn) {
+        const tx = this.api.tx.amm.swapExactOut(tokenIn, tokenOut, amountOut, maxAmountIn);
+        const hash = await tx.signAndSend(keyRingPair);
+        console.log('SwapExactOut sent with hash', hash.toHex());
+    }
+
+    // calls an AmmApi runtime api method taking u128 arguments
+    // and returning an Option<u128>, null if None
+    async callAmmApi(method, ...args) {
+        const params = Util.u8aConcat(...args.map((arg) => this.api.createType('u128', arg).toU8a()));
+        const result = await this.api.rpc.state.call(method, params);
+        const quote = this.api.createType('Option<u128>', result);
+        return quote.isSome ? quote.unwrap() : null;
+    }
+
+    // quotes the amount of tokenOut received for amountIn of tokenIn
+    async quoteExactIn(tokenIn, tokenOut, amountIn) {
+        return this.callAmmApi('AmmApi_quote_exact_in', tokenIn, tokenOut, amountIn);
+    }
+
+    // quotes the amount of tokenIn paid for amountOut of tokenOut
+    async quoteExactOut(tokenIn, tokenOut, amountOut) {
+        return this.callAmmApi('AmmApi_quote_exact_out', tokenIn, tokenOut, amountOut);
+    }
+
     // create a Delegated Transfer Details struct
     async createDelegatedTransferDetails(senderAddress, receiverAddress, amount) {
         const nonce = await this.getNonce(senderAddress);
@@ -161,7 +533,7 @@ class ParrotInterface {
 
     // signs a Delegated Transfer 
     async signDtd(keyRingPair, dtd) {
-        const encodedDtd = dtd.toU8a();
+        const encodedDtd = this.signingPayload('delegation/transfer', dtd);
         const signature = keyRingPair.sign(encodedDtd, { withType: true });
         return signature;
     }
@@ -129,5 +129,7 @@ fn testnet_genesis(
         }),
         sudo: Some(SudoConfig { key: root_key }),
         burn: Some(Default::default()),
+        prc20: Some(Default::default()),
+        delegation: Some(Default::default()),
     }
 }
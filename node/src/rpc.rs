@@ -0,0 +1,24 @@
+//! Node specific RPC methods, added to the default substrate RPC of the
+//! full node.
+
+use std::sync::Arc;
+
+use parrot_runtime::{opaque::Block, prc20::TokenMetadata, AccountId};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+
+/// The RPC extensions of the node.
+pub type IoHandler = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
+
+/// Instantiate the RPC extensions of a full node.
+pub fn create_full<C>(client: Arc<C>) -> IoHandler
+where
+    C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+    C::Api: prc20_rpc::Prc20RuntimeApi<Block, AccountId, u128, u128, TokenMetadata>,
+{
+    use prc20_rpc::{Prc20, Prc20Api};
+
+    let mut io = IoHandler::default();
+    io.extend_with(Prc20Api::to_delegate(Prc20::new(client)));
+    io
+}
@@ -83,6 +83,11 @@ macro_rules! new_full_start {
 
                 Ok(import_queue)
             },
+        )?
+        .with_rpc_extensions(
+            |builder| -> Result<crate::rpc::IoHandler, sc_service::Error> {
+                Ok(crate::rpc::create_full(builder.client().clone()))
+            },
         )?;
 
         (builder, import_setup, inherent_data_providers)
@@ -0,0 +1,43 @@
+/*
+Copyright (C) 2020 OAX Foundation Limited
+
+This program is free software: you can redistribute it and/or modify it under the
+terms of the GNU General Public License as published by the Free Software Foundation,
+either version 3 of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with
+this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#![cfg_attr(not(feature = "std"), no_std)]
+//! # AMM Runtime API
+//! Lets clients quote AMM swaps without simulating extrinsics
+use codec::Codec;
+
+sp_api::decl_runtime_apis! {
+    pub trait AmmApi<TokenId, TokenBalance> where
+        TokenId: Codec,
+        TokenBalance: Codec,
+    {
+        /// Amount of token_out received for amount_in of token_in,
+        /// None if there is no pool or not enough liquidity
+        fn quote_exact_in(
+            token_in: TokenId,
+            token_out: TokenId,
+            amount_in: TokenBalance,
+        ) -> Option<TokenBalance>;
+        /// Amount of token_in paid for amount_out of token_out,
+        /// None if there is no pool or not enough liquidity
+        fn quote_exact_out(
+            token_in: TokenId,
+            token_out: TokenId,
+            amount_out: TokenBalance,
+        ) -> Option<TokenBalance>;
+        /// Reserves of the pool of a pair, in the order of the tokens given
+        fn reserves(token_a: TokenId, token_b: TokenId) -> Option<(TokenBalance, TokenBalance)>;
+    }
+}
@@ -0,0 +1,874 @@
+/*
+Copyright (C) 2020 OAX Foundation Limited
+
+This program is free software: you can redistribute it and/or modify it under the
+terms of the GNU General Public License as published by the Free Software Foundation,
+either version 3 of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with
+this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#![cfg_attr(not(feature = "std"), no_std)]
+//! # AMM Module
+//! Constant product liquidity pools for pairs of PRC20 tokens.
+//! Liquidity providers deposit both tokens of a pair and receive LP shares,
+//! which are a PRC20 token created with the pool. Traders swap against the
+//! pool reserves, paying a fee (SwapFee) that stays in the pool.
+//! The reserves of a pool are the PRC20 balances of its pool account,
+//! which has no private key. The first MINIMUM_LIQUIDITY shares of a pool
+//! are locked in the pool account, and a swap or a withdrawal can't leave
+//! a reserve below the minimum balance of its token.
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage,
+    dispatch::DispatchResult,
+    ensure,
+    traits::{ExistenceRequirement, Get},
+    transactional,
+};
+use frame_system::{self as system, ensure_signed};
+use prc20::TokenMetadata;
+use sp_arithmetic::helpers_128bit::multiply_by_rational;
+use sp_runtime::{
+    traits::{AccountIdConversion, IntegerSquareRoot, Zero},
+    DispatchError, ModuleId, PerThing, Permill,
+};
+use sp_std::{
+    cmp::min,
+    convert::{TryFrom, TryInto},
+};
+
+/// The AMM's module id, used for deriving the pool account IDs.
+const MODULE_ID: ModuleId = ModuleId(*b"py/ammpl");
+
+/// LP shares locked in the pool account by the first deposit,
+/// so the shares of a pool can't be all burned
+pub const MINIMUM_LIQUIDITY: u128 = 1_000;
+
+type TokenBalanceOf<T> = <T as prc20::Trait>::TokenBalance;
+type Prc20<T> = prc20::Module<T>;
+
+/// The module's configuration trait.
+pub trait Trait: frame_system::Trait + prc20::Trait {
+    /// The overarching event type.
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+    /// Fee taken from the input amount of every swap, this is set in lib.rs
+    type SwapFee: Get<Permill>;
+}
+
+// This is used to add custom error types for the amm module
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// a pool needs two different tokens
+        SameToken,
+        /// the token doesn't exist
+        UnknownToken,
+        /// there is already a pool for this pair
+        PoolExists,
+        /// there is no pool for this pair
+        NoPool,
+        /// the amounts are zero or give no LP shares
+        ZeroAmount,
+        /// the pool doesn't have enough liquidity for this swap
+        InsufficientLiquidity,
+        /// the result is worse than the limit given by the sender
+        SlippageExceeded,
+        /// arithmetic overflow
+        Overflow,
+    }
+}
+
+// This module's storage items.
+decl_storage! {
+    trait Store for Module<T: Trait> as Amm {
+        /// this stores the LP token of the pool of each pair,
+        /// pairs are keyed with the lowest token id first
+        Pools get(fn lp_token_of): map hasher(blake2_128_concat)
+            (T::TokenId, T::TokenId) => Option<T::TokenId>;
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        TokenId = <T as prc20::Trait>::TokenId,
+        TokenBalance = <T as prc20::Trait>::TokenBalance,
+    {
+        /// Pool created (tokenA, tokenB, lpTokenId, creator)
+        PoolCreated(TokenId, TokenId, TokenId, AccountId),
+        /// Liquidity added (provider, tokenA, amountA, tokenB, amountB, shares)
+        LiquidityAdded(
+            AccountId,
+            TokenId,
+            TokenBalance,
+            TokenId,
+            TokenBalance,
+            TokenBalance,
+        ),
+        /// Liquidity removed (provider, tokenA, amountA, tokenB, amountB, shares)
+        LiquidityRemoved(
+            AccountId,
+            TokenId,
+            TokenBalance,
+            TokenId,
+            TokenBalance,
+            TokenBalance,
+        ),
+        /// Swap (trader, tokenIn, amountIn, tokenOut, amountOut)
+        Swapped(AccountId, TokenId, TokenBalance, TokenId, TokenBalance),
+    }
+);
+
+// The module's dispatch functions.
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        /// The fee taken from the input amount of every swap
+        const SwapFee: Permill = T::SwapFee::get();
+
+        /// this is needed only if you are using events in your module
+        fn deposit_event() = default;
+
+        /// create the pool of a pair of tokens, the sender pays the
+        /// deposit of the LP token created for the pool
+        #[weight = T::DbWeight::get().reads_writes(4, 8) + 70_000_000]
+        fn create_pool(origin,
+            #[compact] token_a: T::TokenId,
+            #[compact] token_b: T::TokenId
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            ensure!(token_a != token_b, <Error<T>>::SameToken);
+            let token_count = <Prc20<T>>::token_count();
+            ensure!(token_a < token_count && token_b < token_count, <Error<T>>::UnknownToken);
+            let pair = Self::pair(token_a, token_b);
+            ensure!(Self::lp_token_of(pair).is_none(), <Error<T>>::PoolExists);
+            // the LP token is owned by the pool account, so only
+            // this module can mint and burn shares
+            let metadata = TokenMetadata {
+                name: b"Parrot AMM LP".to_vec(),
+                symbol: b"PLP".to_vec(),
+                decimals: 0,
+                uri: None,
+            };
+            let lp_token = <Prc20<T>>::do_create_token(&sender,
+                &Self::pool_account(pair), Zero::zero(), Zero::zero(), metadata)?;
+            <Pools<T>>::insert(pair, lp_token);
+            // broadcast pool created event
+            Self::deposit_event(RawEvent::PoolCreated(pair.0, pair.1, lp_token, sender));
+            Ok(())
+        }
+
+        /// add liquidity to a pool, at most amount_a_desired and amount_b_desired
+        /// are deposited at the current pool ratio, for at least min_shares
+        #[weight = T::DbWeight::get().reads_writes(7, 6) + 70_000_000]
+        fn add_liquidity(origin,
+            #[compact] token_a: T::TokenId,
+            #[compact] token_b: T::TokenId,
+            #[compact] amount_a_desired: T::TokenBalance,
+            #[compact] amount_b_desired: T::TokenBalance,
+            #[compact] min_shares: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::make_add_liquidity(sender, token_a, token_b,
+                amount_a_desired, amount_b_desired, min_shares)
+        }
+
+        /// burn LP shares of a pool for the proportional part of its
+        /// reserves, at least min_amount_a and min_amount_b
+        #[weight = T::DbWeight::get().reads_writes(7, 6) + 70_000_000]
+        fn remove_liquidity(origin,
+            #[compact] token_a: T::TokenId,
+            #[compact] token_b: T::TokenId,
+            #[compact] shares: T::TokenBalance,
+            #[compact] min_amount_a: T::TokenBalance,
+            #[compact] min_amount_b: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::make_remove_liquidity(sender, token_a, token_b,
+                shares, min_amount_a, min_amount_b)
+        }
+
+        /// swap exactly amount_in of token_in for at least min_amount_out of token_out
+        #[weight = T::DbWeight::get().reads_writes(5, 4) + 70_000_000]
+        fn swap_exact_in(origin,
+            #[compact] token_in: T::TokenId,
+            #[compact] token_out: T::TokenId,
+            #[compact] amount_in: T::TokenBalance,
+            #[compact] min_amount_out: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let amount_out = Self::quote_exact_in(token_in, token_out, amount_in)
+                .ok_or(<Error<T>>::InsufficientLiquidity)?;
+            ensure!(!amount_out.is_zero(), <Error<T>>::ZeroAmount);
+            ensure!(amount_out >= min_amount_out, <Error<T>>::SlippageExceeded);
+            Self::make_swap(sender, token_in, amount_in, token_out, amount_out)
+        }
+
+        /// swap at most max_amount_in of token_in for exactly amount_out of token_out
+        #[weight = T::DbWeight::get().reads_writes(5, 4) + 70_000_000]
+        fn swap_exact_out(origin,
+            #[compact] token_in: T::TokenId,
+            #[compact] token_out: T::TokenId,
+            #[compact] amount_out: T::TokenBalance,
+            #[compact] max_amount_in: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            ensure!(!amount_out.is_zero(), <Error<T>>::ZeroAmount);
+            let amount_in = Self::quote_exact_out(token_in, token_out, amount_out)
+                .ok_or(<Error<T>>::InsufficientLiquidity)?;
+            ensure!(amount_in <= max_amount_in, <Error<T>>::SlippageExceeded);
+            Self::make_swap(sender, token_in, amount_in, token_out, amount_out)
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The key of the pool of two tokens, the lowest token id first
+    fn pair(token_a: T::TokenId, token_b: T::TokenId) -> (T::TokenId, T::TokenId) {
+        if token_a < token_b {
+            (token_a, token_b)
+        } else {
+            (token_b, token_a)
+        }
+    }
+
+    /// The account ID holding the reserves of the pool of a pair.
+    pub fn pool_account(pair: (T::TokenId, T::TokenId)) -> T::AccountId {
+        MODULE_ID.into_sub_account(pair)
+    }
+
+    /// Reserves of the pool of two tokens, in the order of the tokens given
+    pub fn reserves(
+        token_a: T::TokenId,
+        token_b: T::TokenId,
+    ) -> Option<(T::TokenBalance, T::TokenBalance)> {
+        let pair = Self::pair(token_a, token_b);
+        Self::lp_token_of(pair)?;
+        let pool = Self::pool_account(pair);
+        Some((
+            <Prc20<T>>::balance_of(token_a, pool.clone()),
+            <Prc20<T>>::balance_of(token_b, pool),
+        ))
+    }
+
+    /// Amount of token_out received for amount_in of token_in
+    pub fn quote_exact_in(
+        token_in: T::TokenId,
+        token_out: T::TokenId,
+        amount_in: T::TokenBalance,
+    ) -> Option<T::TokenBalance> {
+        let (reserve_in, reserve_out) = Self::reserves(token_in, token_out)?;
+        let amount_in = to_u128::<T>(amount_in).ok()?;
+        // the fee stays in the pool, rounded up in favour of the pool
+        let amount_in = amount_in - T::SwapFee::get().mul_ceil(amount_in);
+        let reserve_in = to_u128::<T>(reserve_in).ok()?;
+        let amount_out = multiply_by_rational(
+            amount_in,
+            to_u128::<T>(reserve_out).ok()?,
+            reserve_in.checked_add(amount_in)?,
+        )
+        .ok()?;
+        from_u128::<T>(amount_out).ok()
+    }
+
+    /// Amount of token_in paid for amount_out of token_out,
+    /// rounded up in favour of the pool
+    pub fn quote_exact_out(
+        token_in: T::TokenId,
+        token_out: T::TokenId,
+        amount_out: T::TokenBalance,
+    ) -> Option<T::TokenBalance> {
+        let (reserve_in, reserve_out) = Self::reserves(token_in, token_out)?;
+        let amount_out = to_u128::<T>(amount_out).ok()?;
+        let reserve_out = to_u128::<T>(reserve_out).ok()?;
+        if amount_out >= reserve_out {
+            return None;
+        }
+        let amount_in = mul_div_ceil(
+            to_u128::<T>(reserve_in).ok()?,
+            amount_out,
+            reserve_out - amount_out,
+        )?;
+        // add the fee taken from the input amount
+        let fee_complement = Permill::one().deconstruct() - T::SwapFee::get().deconstruct();
+        let amount_in = mul_div_ceil(
+            amount_in,
+            Permill::one().deconstruct().into(),
+            fee_complement.into(),
+        )?;
+        from_u128::<T>(amount_in).ok()
+    }
+
+    /// moves the tokens of a swap, if the second transfer
+    /// fails the first one is reverted as well
+    #[transactional]
+    fn make_swap(
+        trader: T::AccountId,
+        token_in: T::TokenId,
+        amount_in: T::TokenBalance,
+        token_out: T::TokenId,
+        amount_out: T::TokenBalance,
+    ) -> DispatchResult {
+        let pool = Self::pool_account(Self::pair(token_in, token_out));
+        <Prc20<T>>::ensure_can_send(token_in, &trader)?;
+        <Prc20<T>>::ensure_can_send(token_out, &pool)?;
+        <Prc20<T>>::make_transfer(
+            token_in,
+            trader.clone(),
+            pool.clone(),
+            amount_in,
+            ExistenceRequirement::AllowDeath,
+        )?;
+        // the reserve left in the pool must stay above the minimum balance
+        <Prc20<T>>::make_transfer(
+            token_out,
+            pool,
+            trader.clone(),
+            amount_out,
+            ExistenceRequirement::KeepAlive,
+        )?;
+        // broadcast swapped event
+        Self::deposit_event(RawEvent::Swapped(
+            trader, token_in, amount_in, token_out, amount_out,
+        ));
+        Ok(())
+    }
+
+    /// deposits liquidity and mints the LP shares
+    #[transactional]
+    fn make_add_liquidity(
+        provider: T::AccountId,
+        token_a: T::TokenId,
+        token_b: T::TokenId,
+        amount_a_desired: T::TokenBalance,
+        amount_b_desired: T::TokenBalance,
+        min_shares: T::TokenBalance,
+    ) -> DispatchResult {
+        let pair = Self::pair(token_a, token_b);
+        let lp_token = Self::lp_token_of(pair).ok_or(<Error<T>>::NoPool)?;
+        let (reserve_a, reserve_b) = Self::reserves(token_a, token_b).ok_or(<Error<T>>::NoPool)?;
+        let total_shares = to_u128::<T>(<Prc20<T>>::total_supply(lp_token))?;
+        let (reserve_a, reserve_b) = (to_u128::<T>(reserve_a)?, to_u128::<T>(reserve_b)?);
+        let (desired_a, desired_b) = (
+            to_u128::<T>(amount_a_desired)?,
+            to_u128::<T>(amount_b_desired)?,
+        );
+        let (amount_a, amount_b, shares) =
+            if total_shares.is_zero() || reserve_a.is_zero() || reserve_b.is_zero() {
+                // the first provider sets the price, and the first
+                // MINIMUM_LIQUIDITY shares are locked in the pool
+                let product = desired_a
+                    .checked_mul(desired_b)
+                    .ok_or(<Error<T>>::Overflow)?;
+                let shares = product
+                    .integer_sqrt()
+                    .checked_sub(MINIMUM_LIQUIDITY)
+                    .ok_or(<Error<T>>::ZeroAmount)?;
+                (desired_a, desired_b, shares)
+            } else {
+                // deposit at the current pool ratio
+                let optimal_b = mul_div::<T>(desired_a, reserve_b, reserve_a)?;
+                let (amount_a, amount_b) = if optimal_b <= desired_b {
+                    (desired_a, optimal_b)
+                } else {
+                    (mul_div::<T>(desired_b, reserve_a, reserve_b)?, desired_b)
+                };
+                let shares = min(
+                    mul_div::<T>(amount_a, total_shares, reserve_a)?,
+                    mul_div::<T>(amount_b, total_shares, reserve_b)?,
+                );
+                (amount_a, amount_b, shares)
+            };
+        ensure!(!shares.is_zero(), <Error<T>>::ZeroAmount);
+        let (amount_a, amount_b, shares) = (
+            from_u128::<T>(amount_a)?,
+            from_u128::<T>(amount_b)?,
+            from_u128::<T>(shares)?,
+        );
+        ensure!(shares >= min_shares, <Error<T>>::SlippageExceeded);
+        let pool = Self::pool_account(pair);
+        <Prc20<T>>::ensure_can_send(token_a, &provider)?;
+        <Prc20<T>>::ensure_can_send(token_b, &provider)?;
+        <Prc20<T>>::make_transfer(
+            token_a,
+            provider.clone(),
+            pool.clone(),
+            amount_a,
+            ExistenceRequirement::AllowDeath,
+        )?;
+        <Prc20<T>>::make_transfer(
+            token_b,
+            provider.clone(),
+            pool.clone(),
+            amount_b,
+            ExistenceRequirement::AllowDeath,
+        )?;
+        if total_shares.is_zero() {
+            <Prc20<T>>::do_mint(lp_token, &pool, from_u128::<T>(MINIMUM_LIQUIDITY)?)?;
+        }
+        <Prc20<T>>::do_mint(lp_token, &provider, shares)?;
+        // broadcast liquidity added event
+        Self::deposit_event(RawEvent::LiquidityAdded(
+            provider, token_a, amount_a, token_b, amount_b, shares,
+        ));
+        Ok(())
+    }
+
+    /// burns LP shares and withdraws the proportional part of the reserves
+    #[transactional]
+    fn make_remove_liquidity(
+        provider: T::AccountId,
+        token_a: T::TokenId,
+        token_b: T::TokenId,
+        shares: T::TokenBalance,
+        min_amount_a: T::TokenBalance,
+        min_amount_b: T::TokenBalance,
+    ) -> DispatchResult {
+        let pair = Self::pair(token_a, token_b);
+        let lp_token = Self::lp_token_of(pair).ok_or(<Error<T>>::NoPool)?;
+        let (reserve_a, reserve_b) = Self::reserves(token_a, token_b).ok_or(<Error<T>>::NoPool)?;
+        let total_shares = to_u128::<T>(<Prc20<T>>::total_supply(lp_token))?;
+        ensure!(
+            !shares.is_zero() && !total_shares.is_zero(),
+            <Error<T>>::ZeroAmount
+        );
+        let burned = to_u128::<T>(shares)?;
+        let amount_a = from_u128::<T>(mul_div::<T>(
+            burned,
+            to_u128::<T>(reserve_a)?,
+            total_shares,
+        )?)?;
+        let amount_b = from_u128::<T>(mul_div::<T>(
+            burned,
+            to_u128::<T>(reserve_b)?,
+            total_shares,
+        )?)?;
+        ensure!(
+            amount_a >= min_amount_a && amount_b >= min_amount_b,
+            <Error<T>>::SlippageExceeded
+        );
+        let pool = Self::pool_account(pair);
+        <Prc20<T>>::ensure_can_send(token_a, &pool)?;
+        <Prc20<T>>::ensure_can_send(token_b, &pool)?;
+        <Prc20<T>>::do_burn(lp_token, &provider, shares)?;
+        // the reserves left in the pool must stay above the minimum balance
+        <Prc20<T>>::make_transfer(
+            token_a,
+            pool.clone(),
+            provider.clone(),
+            amount_a,
+            ExistenceRequirement::KeepAlive,
+        )?;
+        <Prc20<T>>::make_transfer(
+            token_b,
+            pool,
+            provider.clone(),
+            amount_b,
+            ExistenceRequirement::KeepAlive,
+        )?;
+        // broadcast liquidity removed event
+        Self::deposit_event(RawEvent::LiquidityRemoved(
+            provider, token_a, amount_a, token_b, amount_b, shares,
+        ));
+        Ok(())
+    }
+}
+
+/// Converts a token amount to u128 for the pool arithmetic
+fn to_u128<T: Trait>(amount: TokenBalanceOf<T>) -> Result<u128, DispatchError> {
+    TryInto::<u128>::try_into(amount).map_err(|_| <Error<T>>::Overflow.into())
+}
+
+/// Converts a result of the pool arithmetic back to a token amount
+fn from_u128<T: Trait>(amount: u128) -> Result<TokenBalanceOf<T>, DispatchError> {
+    TokenBalanceOf::<T>::try_from(amount).map_err(|_| <Error<T>>::Overflow.into())
+}
+
+/// a * b / c rounded down, computed without intermediate overflow
+fn mul_div<T: Trait>(a: u128, b: u128, c: u128) -> Result<u128, DispatchError> {
+    multiply_by_rational(a, b, c).map_err(|_| <Error<T>>::Overflow.into())
+}
+
+/// a * b / c rounded up, None on overflow or division by zero
+fn mul_div_ceil(a: u128, b: u128, c: u128) -> Option<u128> {
+    let amount = multiply_by_rational(a, b, c).ok()?;
+    match a.checked_mul(b) {
+        Some(product) if amount * c == product => Some(amount),
+        // round up, also when the product doesn't fit
+        _ => amount.checked_add(1),
+    }
+}
+
+// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{
+        assert_noop, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+        weights::Weight,
+    };
+    use sp_core::sr25519;
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup, Verify},
+        Perbill,
+    };
+    use substrate_test_client::{self, AccountKeyring};
+
+    mod amm {
+        pub use super::super::*;
+    }
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    impl_outer_event! {
+        pub enum Event for Test {
+            system<T>,
+            pallet_balances<T>,
+            prc20<T>,
+            amm<T>,
+        }
+    }
+
+    /// The signature type used by accounts/transactions.
+    pub type Signature = sr25519::Signature;
+    /// An identifier for an account on this system.
+    pub type AccountId = <Signature as Verify>::Signer;
+
+    // implement frame_system trait for test
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::one();
+    }
+    impl frame_system::Trait for Test {
+        type Origin = Origin;
+        type BaseCallFilter = ();
+        type Index = u64;
+        type BlockNumber = u64;
+        type Call = ();
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = AccountId;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = Event;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type DbWeight = ();
+        type BlockExecutionWeight = ();
+        type ExtrinsicBaseWeight = ();
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type MaximumBlockLength = MaximumBlockLength;
+        type MaximumExtrinsicWeight = MaximumBlockWeight;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    parameter_types! {
+        pub const ExistentialDeposit: u64 = 1;
+    }
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type Event = Event;
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+        type DustRemoval = ();
+    }
+
+    parameter_types! {
+        pub const MaxTransfers: u8 = 100;
+        pub const TokenDeposit: u64 = 10;
+        pub const MaxNameLength: u32 = 32;
+        pub const MaxSymbolLength: u32 = 8;
+        pub const MaxUriLength: u32 = 128;
+        pub const MaxBatchSize: u32 = 10;
+        pub const MaxOffersPerBlock: u32 = 10;
+    }
+    impl prc20::Trait for Test {
+        type Event = Event;
+        type Currency = pallet_balances::Module<Self>;
+        type TokenDeposit = TokenDeposit;
+        type TokenBalance = u128;
+        type TokenId = u128;
+        type Public = AccountId;
+        type Signature = Signature;
+        type MaxTransfers = MaxTransfers;
+        type MaxNameLength = MaxNameLength;
+        type MaxSymbolLength = MaxSymbolLength;
+        type MaxUriLength = MaxUriLength;
+        type MaxBatchSize = MaxBatchSize;
+        type MaxOffersPerBlock = MaxOffersPerBlock;
+    }
+
+    parameter_types! {
+        // 0.3% swap fee
+        pub const SwapFee: Permill = Permill::from_parts(3_000);
+    }
+    impl Trait for Test {
+        type Event = Event;
+        type SwapFee = SwapFee;
+    }
+
+    type System = frame_system::Module<Test>;
+    type PRC20 = prc20::Module<Test>;
+    type AMM = Module<Test>;
+
+    pub struct ExtBuilder;
+
+    impl ExtBuilder {
+        pub fn build() -> sp_io::TestExternalities {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let mut t = system::GenesisConfig::default()
+                .build_storage::<Test>()
+                .unwrap();
+            pallet_balances::GenesisConfig::<Test> {
+                balances: vec![(alice, 1000), (bob, 1000)],
+            }
+            .assimilate_storage(&mut t)
+            .unwrap();
+            t.into()
+        }
+    }
+
+    // Alice creates tokens 0 and 1, gives 10000 of each to Bob,
+    // then creates the pool of the pair (LP token 2)
+    fn setup_pool() -> (AccountId, AccountId) {
+        let alice = AccountId::from(AccountKeyring::Alice);
+        let bob = AccountId::from(AccountKeyring::Bob);
+        for id in 0..2 {
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                100_000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                id,
+                10_000
+            ));
+        }
+        assert_ok!(AMM::create_pool(Origin::signed(alice.clone()), 1, 0));
+        (alice, bob)
+    }
+
+    #[test]
+    fn create_pool_works() {
+        ExtBuilder::build().execute_with(|| {
+            let (alice, _) = setup_pool();
+            assert_eq!(AMM::lp_token_of((0, 1)), Some(2));
+            assert_eq!(PRC20::owner_of(2), Some(AMM::pool_account((0, 1))));
+            assert_noop!(
+                AMM::create_pool(Origin::signed(alice.clone()), 0, 1),
+                Error::<Test>::PoolExists
+            );
+            assert_noop!(
+                AMM::create_pool(Origin::signed(alice.clone()), 0, 0),
+                Error::<Test>::SameToken
+            );
+            assert_noop!(
+                AMM::create_pool(Origin::signed(alice), 0, 5),
+                Error::<Test>::UnknownToken
+            );
+        });
+    }
+
+    #[test]
+    fn add_and_remove_liquidity_works() {
+        ExtBuilder::build().execute_with(|| {
+            let (alice, bob) = setup_pool();
+            // Alice sets the price at 1 token 0 for 4 token 1
+            assert_ok!(AMM::add_liquidity(
+                Origin::signed(alice.clone()),
+                0,
+                1,
+                1000,
+                4000,
+                0
+            ));
+            // the first 1000 of the 2000 shares are locked in the pool
+            let pool = AMM::pool_account((0, 1));
+            assert_eq!(PRC20::balance_of(2, alice.clone()), 1000);
+            assert_eq!(PRC20::balance_of(2, pool), MINIMUM_LIQUIDITY);
+            assert_eq!(AMM::reserves(0, 1), Some((1000, 4000)));
+            // Bob deposits at the pool ratio, the extra token 1 is not taken
+            assert_noop!(
+                AMM::add_liquidity(Origin::signed(bob.clone()), 0, 1, 500, 3000, 1001),
+                Error::<Test>::SlippageExceeded
+            );
+            assert_ok!(AMM::add_liquidity(
+                Origin::signed(bob.clone()),
+                0,
+                1,
+                500,
+                3000,
+                1000
+            ));
+            assert_eq!(PRC20::balance_of(2, bob.clone()), 1000);
+            assert_eq!(AMM::reserves(1, 0), Some((6000, 1500)));
+            assert_eq!(PRC20::balance_of(1, bob.clone()), 8000);
+            // Bob withdraws his share of the pool
+            assert_ok!(AMM::remove_liquidity(
+                Origin::signed(bob.clone()),
+                0,
+                1,
+                1000,
+                500,
+                2000
+            ));
+            assert_eq!(PRC20::balance_of(2, bob.clone()), 0);
+            assert_eq!(PRC20::total_supply(2), 2000);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 10_000);
+            assert_eq!(PRC20::balance_of(1, bob), 10_000);
+            assert_eq!(AMM::reserves(0, 1), Some((1000, 4000)));
+        });
+    }
+
+    #[test]
+    fn swaps_work() {
+        ExtBuilder::build().execute_with(|| {
+            let (alice, bob) = setup_pool();
+            assert_ok!(AMM::add_liquidity(
+                Origin::signed(alice),
+                0,
+                1,
+                10_000,
+                10_000,
+                0
+            ));
+            // the fee of 100 in is rounded up: 99 * 10000 / 10099 = 98
+            assert_eq!(AMM::quote_exact_in(0, 1, 100), Some(98));
+            // 1000 in, 997 after the fee: 997 * 10000 / 10997 = 906
+            assert_eq!(AMM::quote_exact_in(0, 1, 1000), Some(906));
+            assert_noop!(
+                AMM::swap_exact_in(Origin::signed(bob.clone()), 0, 1, 1000, 907),
+                Error::<Test>::SlippageExceeded
+            );
+            assert_ok!(AMM::swap_exact_in(
+                Origin::signed(bob.clone()),
+                0,
+                1,
+                1000,
+                906
+            ));
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 9000);
+            assert_eq!(PRC20::balance_of(1, bob.clone()), 10_906);
+            assert_eq!(AMM::reserves(0, 1), Some((11_000, 9094)));
+            // buy back exactly 1000 token 0:
+            // 9094 * 1000 / 10000 = 909.4 -> 910, / 0.997 -> 913
+            assert_eq!(AMM::quote_exact_out(1, 0, 1000), Some(913));
+            assert_noop!(
+                AMM::swap_exact_out(Origin::signed(bob.clone()), 1, 0, 1000, 912),
+                Error::<Test>::SlippageExceeded
+            );
+            assert_ok!(AMM::swap_exact_out(
+                Origin::signed(bob.clone()),
+                1,
+                0,
+                1000,
+                913
+            ));
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 10_000);
+            assert_eq!(PRC20::balance_of(1, bob), 9993);
+            // the pool can't be drained
+            assert_eq!(AMM::quote_exact_out(1, 0, 10_000), None);
+        });
+    }
+
+    #[test]
+    fn first_deposit_must_exceed_the_locked_shares() {
+        ExtBuilder::build().execute_with(|| {
+            let (alice, _) = setup_pool();
+            // sqrt(100 * 100) = 100 shares, less than the locked shares
+            assert_noop!(
+                AMM::add_liquidity(Origin::signed(alice), 0, 1, 100, 100, 0),
+                Error::<Test>::ZeroAmount
+            );
+        });
+    }
+
+    #[test]
+    fn paused_pool_token_can_not_be_withdrawn() {
+        ExtBuilder::build().execute_with(|| {
+            let (alice, _) = setup_pool();
+            assert_ok!(AMM::add_liquidity(
+                Origin::signed(alice.clone()),
+                0,
+                1,
+                10_000,
+                10_000,
+                0
+            ));
+            assert_ok!(PRC20::pause(Origin::signed(alice.clone()), 0));
+            assert_noop!(
+                AMM::remove_liquidity(Origin::signed(alice.clone()), 0, 1, 1000, 0, 0),
+                prc20::Error::<Test>::TokenPaused
+            );
+            assert_ok!(PRC20::unpause(Origin::signed(alice.clone()), 0));
+            assert_ok!(AMM::remove_liquidity(
+                Origin::signed(alice),
+                0,
+                1,
+                1000,
+                0,
+                0
+            ));
+        });
+    }
+
+    #[test]
+    fn reserves_stay_above_the_minimum_balance() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            // token 1 has a minimum balance of 500
+            for min_balance in vec![0, 500] {
+                assert_ok!(PRC20::create_token(
+                    Origin::signed(alice.clone()),
+                    100_000,
+                    min_balance,
+                    TokenMetadata::default()
+                ));
+            }
+            assert_ok!(AMM::create_pool(Origin::signed(alice.clone()), 0, 1));
+            assert_ok!(AMM::add_liquidity(
+                Origin::signed(alice.clone()),
+                0,
+                1,
+                10_000,
+                1000,
+                0
+            ));
+            // buying 600 token 1 would leave 400 in the pool
+            assert_noop!(
+                AMM::swap_exact_out(Origin::signed(alice.clone()), 0, 1, 600, 20_000),
+                prc20::Error::<Test>::KeepAlive
+            );
+            assert_ok!(AMM::swap_exact_out(
+                Origin::signed(alice),
+                0,
+                1,
+                500,
+                20_000
+            ));
+            assert_eq!(AMM::reserves(0, 1).map(|(_, reserve)| reserve), Some(500));
+        });
+    }
+}
@@ -20,16 +20,19 @@ this program. If not, see <http://www.gnu.org/licenses/>.
 //! If the fee delegator wants to broadcast this message he
 //! may choose to do so, he will be charged a fee for the users transfer
 //!  instead of the user This basically achieves a free transfer for the user
+//! The user signs the transfer tag, the genesis hash and the spec name of the
+//! chain followed by the transfer, so the signature is only valid on one chain
 use codec::{Decode, Encode};
 use frame_support::{
-    decl_error, decl_event, decl_module,
+    decl_error, decl_event, decl_module, decl_storage,
     dispatch::DispatchResult,
     ensure,
     traits::{Currency, ExistenceRequirement, Get},
+    weights::Weight,
 };
 use frame_system::{self as system, ensure_signed};
-use sp_runtime::traits::{IdentifyAccount, Member, Verify};
-use sp_std::{convert::TryInto, if_std};
+use sp_runtime::traits::{IdentifyAccount, Member, Verify, Zero};
+use sp_std::{convert::TryInto, if_std, vec::Vec};
 
 /// Types necessary to enable using currency
 type BalanceOf<T> =
@@ -44,8 +47,15 @@ pub trait Trait: frame_system::Trait + pallet_balances::Trait {
     /// Additional types for verifying offline signatures in delegated methods
     type Public: IdentifyAccount<AccountId = Self::AccountId>;
     type Signature: Verify<Signer = Self::Public> + Member + Decode + Encode;
+    /// Number of blocks after the runtime upgrade binding signatures to the
+    /// chain during which signatures over the bare transfer (without chain
+    /// and message tag) are still accepted
+    type LegacySignatureWindow: Get<Self::BlockNumber>;
 }
 
+/// Tag of the signed delegated transfers
+pub const DELEGATED_TRANSFER_TAG: &[u8] = b"delegation/transfer";
+
 /// This is used to encode each transfer, for a delegated Transfer
 #[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
 //#[cfg_attr(feature = "std", derive(Debug))]
@@ -75,6 +85,17 @@ decl_error! {
     }
 }
 
+// This module's storage items.
+decl_storage! {
+    trait Store for Module<T: Trait> as Delegation {
+        /// this stores the last block at which signatures over the bare
+        /// transfer are accepted, set by the runtime upgrade binding signatures
+        /// to the chain, chains created after it never accept them
+        LegacySignatureDeadline get(fn legacy_signature_deadline)
+            build(|_| Zero::zero()): T::BlockNumber;
+    }
+}
+
 // The module's dispatch functions.
 decl_module! {
     /// The module declaration.
@@ -83,6 +104,20 @@ decl_module! {
         /// this is needed only if you are using events in your module
         fn deposit_event() = default;
 
+        /// Open the legacy signature window after a runtime upgrade
+        fn on_runtime_upgrade() -> Weight {
+            // a chain created before signatures were bound to it keeps
+            // accepting the legacy signatures for a while
+            if <LegacySignatureDeadline<T>>::exists() {
+                T::DbWeight::get().reads(1)
+            } else {
+                let now = <system::Module<T>>::block_number();
+                let deadline = now.saturating_add(T::LegacySignatureWindow::get());
+                <LegacySignatureDeadline<T>>::put(deadline);
+                T::DbWeight::get().reads_writes(2, 1)
+            }
+        }
+
         /// Delegated transfer
         #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
         fn delegated_transfer(origin,
@@ -151,14 +186,23 @@ impl<T: Trait> Module<T> {
     fn verify_dtd_signature(
         signed_dtd: SignedDelegatedTransferDetails<T::Signature, T::AccountId, BalanceOf<T>>,
     ) -> Result<(), &'static str> {
-        match signed_dtd
-            .signature
-            .verify(&signed_dtd.transfer.encode()[..], &signed_dtd.signer)
-        {
+        let signature = &signed_dtd.signature;
+        let signer = &signed_dtd.signer;
+        let payload = Self::signing_payload(DELEGATED_TRANSFER_TAG, &signed_dtd.transfer);
+        // signatures over the bare transfer are accepted until the deadline
+        let legacy = <system::Module<T>>::block_number() <= Self::legacy_signature_deadline()
+            && signature.verify(&signed_dtd.transfer.encode()[..], signer);
+        match signature.verify(&payload[..], signer) || legacy {
             true => Ok(()),
             false => Err("signature is invalid"),
         }
     }
+
+    /// The bytes signed offline for a message of this module, see
+    /// `signing::signing_payload`
+    pub fn signing_payload<M: Encode>(tag: &[u8], message: &M) -> Vec<u8> {
+        signing::signing_payload::<T, M>(tag, message)
+    }
 }
 
 // tests for this module
@@ -167,7 +211,7 @@ mod tests {
     use super::*;
     use frame_support::{
         assert_noop, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
-        weights::Weight,
+        traits::OnRuntimeUpgrade,
     };
     use sp_core::sr25519;
     use sp_core::H256;
@@ -240,9 +284,11 @@ mod tests {
         type Public = AccountId;
         type Signature = Signature;
         type Currency = pallet_balances::Module<Self>;
+        type LegacySignatureWindow = LegacySignatureWindow;
     }
 
     parameter_types! {
+        pub const LegacySignatureWindow: u64 = 5;
         pub const ExistentialDeposit: u64 = 1;
         pub const TransferFee: u64 = 0;
         pub const CreationFee: u64 = 0;
@@ -306,7 +352,10 @@ mod tests {
             let signed_dtd = SignedDelegatedTransferDetails {
                 transfer: dtd.clone(),
                 signer: bob.clone(),
-                signature: Signature::from(bob_keyring.sign(&dtd.encode())),
+                signature: Signature::from(bob_keyring.sign(&DelegatorModule::signing_payload(
+                    DELEGATED_TRANSFER_TAG,
+                    &dtd,
+                ))),
             };
             // Fee delegator Alice broadcasts this transaction for Bob
             assert_ok!(DelegatorModule::delegated_transfer(
@@ -355,7 +404,10 @@ mod tests {
             let signed_dtd = SignedDelegatedTransferDetails {
                 transfer: dtd.clone(),
                 signer: eve.clone(),
-                signature: Signature::from(eve_keyring.sign(&dtd.encode())),
+                signature: Signature::from(eve_keyring.sign(&DelegatorModule::signing_payload(
+                    DELEGATED_TRANSFER_TAG,
+                    &dtd,
+                ))),
             };
             // Fee delegator Alice broadcasts this transaction for Eve
             // Do a multi_transfer, assert it errors saying incorrect nonce
@@ -400,7 +452,10 @@ mod tests {
             let signed_dtd = SignedDelegatedTransferDetails {
                 transfer: dtd.clone(),
                 signer: eve.clone(),
-                signature: Signature::from(bob_keyring.sign(&dtd.encode())),
+                signature: Signature::from(bob_keyring.sign(&DelegatorModule::signing_payload(
+                    DELEGATED_TRANSFER_TAG,
+                    &dtd,
+                ))),
             };
             // Fee delegator Alice broadcasts this transaction for Eve
             // Do a multi_transfer, assert it errors saying wrong signature
@@ -416,4 +471,46 @@ mod tests {
             assert_eq!(Balances::free_balance(alice.clone()), alice_balance);
         });
     }
+
+    #[test]
+    fn delegated_transfer_legacy_signature_expires() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let eve = AccountId::from(AccountKeyring::Eve);
+            let bob_keyring = AccountKeyring::Bob;
+            // Bob signs the bare transfer, like clients did before
+            let sign = |nonce: u128| {
+                let dtd = DelegatedTransferDetails {
+                    amount: 100,
+                    to: eve.clone(),
+                    nonce,
+                };
+                SignedDelegatedTransferDetails {
+                    transfer: dtd.clone(),
+                    signer: bob.clone(),
+                    signature: Signature::from(bob_keyring.sign(&dtd.encode())),
+                }
+            };
+            // this is accepted for 5 blocks after the upgrade
+            // binding signatures to the chain
+            System::set_block_number(2);
+            DelegatorModule::on_runtime_upgrade();
+            assert_eq!(DelegatorModule::legacy_signature_deadline(), 7);
+            System::set_block_number(7);
+            assert_ok!(DelegatorModule::delegated_transfer(
+                Origin::signed(alice.clone()),
+                sign(0)
+            ));
+            // later upgrades don't move the deadline
+            DelegatorModule::on_runtime_upgrade();
+            assert_eq!(DelegatorModule::legacy_signature_deadline(), 7);
+            System::set_block_number(8);
+            assert_noop!(
+                DelegatorModule::delegated_transfer(Origin::signed(alice), sign(1)),
+                Error::<Test>::InvalidSignature
+            );
+            assert_eq!(Balances::free_balance(eve), 100);
+        });
+    }
 }
@@ -0,0 +1,297 @@
+/*
+Copyright (C) 2020 OAX Foundation Limited
+
+This program is free software: you can redistribute it and/or modify it under the
+terms of the GNU General Public License as published by the Free Software Foundation,
+either version 3 of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with
+this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! # PRC20 RPC
+//! JSON-RPC methods reading token balances, allowances and metadata
+//! through the PRC20 runtime API.
+//! Token balances and token ids are returned as decimal strings, a JSON
+//! number can't hold every u128 and most clients parse them as doubles.
+use codec::Codec;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+pub use prc20_runtime_api::Prc20Api as Prc20RuntimeApi;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+/// Error code returned when the runtime API call fails
+const RUNTIME_ERROR: i64 = 1;
+
+/// PRC20 RPC methods, every method reads the best block if no block hash is given
+#[rpc]
+pub trait Prc20Api<BlockHash, AccountId, TokenId, TokenBalance, TokenMetadata> {
+    /// Balance of a token held by an account
+    #[rpc(name = "prc20_balanceOf")]
+    fn balance_of(&self, token: TokenId, who: AccountId, at: Option<BlockHash>) -> Result<String>;
+
+    /// Amount of a token the spender can transfer from the owner
+    #[rpc(name = "prc20_allowanceOf")]
+    fn allowance_of(
+        &self,
+        token: TokenId,
+        owner: AccountId,
+        spender: AccountId,
+        at: Option<BlockHash>,
+    ) -> Result<String>;
+
+    /// Total supply of a token
+    #[rpc(name = "prc20_totalSupply")]
+    fn total_supply(&self, token: TokenId, at: Option<BlockHash>) -> Result<String>;
+
+    /// Number of tokens created
+    #[rpc(name = "prc20_tokenCount")]
+    fn token_count(&self, at: Option<BlockHash>) -> Result<String>;
+
+    /// Metadata of a token, null if the token does not exist
+    #[rpc(name = "prc20_tokenMetadata")]
+    fn token_metadata(
+        &self,
+        token: TokenId,
+        at: Option<BlockHash>,
+    ) -> Result<Option<TokenMetadata>>;
+
+    /// Tokens held by an account with their balances, lowest token id first,
+    /// skipping the first `start` tokens and returning at most `limit`
+    #[rpc(name = "prc20_tokensOf")]
+    fn tokens_of(
+        &self,
+        who: AccountId,
+        start: u32,
+        limit: u32,
+        at: Option<BlockHash>,
+    ) -> Result<Vec<(String, String)>>;
+
+    /// Number of accounts holding a token
+    #[rpc(name = "prc20_holderCount")]
+    fn holder_count(&self, token: TokenId, at: Option<BlockHash>) -> Result<u32>;
+
+    /// Largest holders of a token with their balances, largest first
+    #[rpc(name = "prc20_topHolders")]
+    fn top_holders(
+        &self,
+        token: TokenId,
+        count: u32,
+        at: Option<BlockHash>,
+    ) -> Result<Vec<(AccountId, String)>>;
+
+    /// Id of the last snapshot taken of a token, 0 if none
+    #[rpc(name = "prc20_currentSnapshot")]
+    fn current_snapshot(&self, token: TokenId, at: Option<BlockHash>) -> Result<u32>;
+
+    /// Balance of an account at a snapshot, null if the snapshot was not taken
+    #[rpc(name = "prc20_balanceOfAt")]
+    fn balance_of_at(
+        &self,
+        token: TokenId,
+        who: AccountId,
+        snapshot: u32,
+        at: Option<BlockHash>,
+    ) -> Result<Option<String>>;
+
+    /// Total supply of a token at a snapshot, null if the snapshot was not taken
+    #[rpc(name = "prc20_totalSupplyAt")]
+    fn total_supply_at(
+        &self,
+        token: TokenId,
+        snapshot: u32,
+        at: Option<BlockHash>,
+    ) -> Result<Option<String>>;
+}
+
+/// Implements the PRC20 RPC methods with a client providing the runtime API
+pub struct Prc20<C, Block> {
+    client: Arc<C>,
+    _marker: PhantomData<Block>,
+}
+
+impl<C, Block> Prc20<C, Block> {
+    /// Creates the PRC20 RPC handler
+    pub fn new(client: Arc<C>) -> Self {
+        Prc20 {
+            client,
+            _marker: Default::default(),
+        }
+    }
+}
+
+/// Converts a runtime API error to an RPC error
+fn runtime_error(message: &str, error: impl Debug) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(RUNTIME_ERROR),
+        message: message.into(),
+        data: Some(format!("{:?}", error).into()),
+    }
+}
+
+impl<C, Block, AccountId, TokenId, TokenBalance, TokenMetadata>
+    Prc20Api<<Block as BlockT>::Hash, AccountId, TokenId, TokenBalance, TokenMetadata>
+    for Prc20<C, Block>
+where
+    Block: BlockT,
+    C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: Prc20RuntimeApi<Block, AccountId, TokenId, TokenBalance, TokenMetadata>,
+    AccountId: Codec,
+    TokenId: Codec + Display,
+    TokenBalance: Codec + Display,
+    TokenMetadata: Codec,
+{
+    fn balance_of(
+        &self,
+        token: TokenId,
+        who: AccountId,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<String> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .balance_of(&at, token, who)
+            .map(|amount| amount.to_string())
+            .map_err(|e| runtime_error("Unable to query balance.", e))
+    }
+
+    fn allowance_of(
+        &self,
+        token: TokenId,
+        owner: AccountId,
+        spender: AccountId,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<String> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .allowance_of(&at, token, owner, spender)
+            .map(|amount| amount.to_string())
+            .map_err(|e| runtime_error("Unable to query allowance.", e))
+    }
+
+    fn total_supply(&self, token: TokenId, at: Option<<Block as BlockT>::Hash>) -> Result<String> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .total_supply(&at, token)
+            .map(|amount| amount.to_string())
+            .map_err(|e| runtime_error("Unable to query total supply.", e))
+    }
+
+    fn token_count(&self, at: Option<<Block as BlockT>::Hash>) -> Result<String> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .token_count(&at)
+            .map(|amount| amount.to_string())
+            .map_err(|e| runtime_error("Unable to query token count.", e))
+    }
+
+    fn token_metadata(
+        &self,
+        token: TokenId,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Option<TokenMetadata>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .token_metadata(&at, token)
+            .map_err(|e| runtime_error("Unable to query token metadata.", e))
+    }
+
+    fn tokens_of(
+        &self,
+        who: AccountId,
+        start: u32,
+        limit: u32,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Vec<(String, String)>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .tokens_of(&at, who, start, limit)
+            .map(|tokens| {
+                tokens
+                    .into_iter()
+                    .map(|(token, balance)| (token.to_string(), balance.to_string()))
+                    .collect()
+            })
+            .map_err(|e| runtime_error("Unable to query account tokens.", e))
+    }
+
+    fn holder_count(&self, token: TokenId, at: Option<<Block as BlockT>::Hash>) -> Result<u32> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .holder_count(&at, token)
+            .map_err(|e| runtime_error("Unable to query holder count.", e))
+    }
+
+    fn top_holders(
+        &self,
+        token: TokenId,
+        count: u32,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Vec<(AccountId, String)>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .top_holders(&at, token, count)
+            .map(|holders| {
+                holders
+                    .into_iter()
+                    .map(|(who, balance)| (who, balance.to_string()))
+                    .collect()
+            })
+            .map_err(|e| runtime_error("Unable to query top holders.", e))
+    }
+
+    fn current_snapshot(&self, token: TokenId, at: Option<<Block as BlockT>::Hash>) -> Result<u32> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .current_snapshot(&at, token)
+            .map_err(|e| runtime_error("Unable to query current snapshot.", e))
+    }
+
+    fn balance_of_at(
+        &self,
+        token: TokenId,
+        who: AccountId,
+        snapshot: u32,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Option<String>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .balance_of_at(&at, token, who, snapshot)
+            .map(|balance| balance.map(|balance| balance.to_string()))
+            .map_err(|e| runtime_error("Unable to query snapshot balance.", e))
+    }
+
+    fn total_supply_at(
+        &self,
+        token: TokenId,
+        snapshot: u32,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Option<String>> {
+        let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+        self.client
+            .runtime_api()
+            .total_supply_at(&at, token, snapshot)
+            .map(|balance| balance.map(|balance| balance.to_string()))
+            .map_err(|e| runtime_error("Unable to query snapshot total supply.", e))
+    }
+}
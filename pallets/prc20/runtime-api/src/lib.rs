@@ -0,0 +1,56 @@
+/*
+Copyright (C) 2020 OAX Foundation Limited
+
+This program is free software: you can redistribute it and/or modify it under the
+terms of the GNU General Public License as published by the Free Software Foundation,
+either version 3 of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with
+this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#![cfg_attr(not(feature = "std"), no_std)]
+//! # PRC20 Runtime API
+//! Lets clients read token balances, allowances and metadata
+//! without building storage keys
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    pub trait Prc20Api<AccountId, TokenId, TokenBalance, TokenMetadata> where
+        AccountId: Codec,
+        TokenId: Codec,
+        TokenBalance: Codec,
+        TokenMetadata: Codec,
+    {
+        /// Balance of a token held by an account
+        fn balance_of(token: TokenId, who: AccountId) -> TokenBalance;
+        /// Amount of a token the spender can transfer from the owner,
+        /// zero once the allowance has expired
+        fn allowance_of(token: TokenId, owner: AccountId, spender: AccountId) -> TokenBalance;
+        /// Total supply of a token
+        fn total_supply(token: TokenId) -> TokenBalance;
+        /// Number of tokens created, token ids go from 0 to token_count - 1
+        fn token_count() -> TokenId;
+        /// Metadata of a token, None if the token does not exist
+        fn token_metadata(token: TokenId) -> Option<TokenMetadata>;
+        /// Tokens held by an account with their balances, lowest token id
+        /// first, skipping the first `start` tokens and returning at most `limit`
+        fn tokens_of(who: AccountId, start: u32, limit: u32) -> Vec<(TokenId, TokenBalance)>;
+        /// Number of accounts holding a token
+        fn holder_count(token: TokenId) -> u32;
+        /// Largest holders of a token with their balances, largest first,
+        /// at most `count` of them (bounded by the runtime)
+        fn top_holders(token: TokenId, count: u32) -> Vec<(AccountId, TokenBalance)>;
+        /// Id of the last snapshot taken of a token, 0 if none
+        fn current_snapshot(token: TokenId) -> u32;
+        /// Balance of an account at a snapshot, None if the snapshot was not taken
+        fn balance_of_at(token: TokenId, who: AccountId, snapshot: u32) -> Option<TokenBalance>;
+        /// Total supply of a token at a snapshot, None if the snapshot was not taken
+        fn total_supply_at(token: TokenId, snapshot: u32) -> Option<TokenBalance>;
+    }
+}
@@ -19,25 +19,73 @@ this program. If not, see <http://www.gnu.org/licenses/>.
 //! + a few extra handy features!
 //! Additional Features:
 //! 1) Atomic Swap :
-//!     Allows swapping tokens with another user in a single tx
+//!     Allows swapping tokens with another user in a single tx,
+//!     offers marked partial_fill can be filled by several takers.
+//!     Offers are tracked by hash instead of the maker's account nonce, so a
+//!     maker can have many offers open, each expiring at its own block
+//! Offline signatures (offers and permits) are over the message tag, the
+//! genesis hash and the spec name of the chain followed by the message, so a
+//! signature is only valid for one chain and one message type, see
+//! `signing_payload`
 //! 2) Multi-transfer:
 //! Allows transferring tokens to multiple users, in one single tx
+//! Creating a token reserves a deposit of the native currency from the
+//! creator, which is returned when the owner destroys the token after
+//! burning its whole supply
+//! Balances that drop to zero are removed from storage, and a token may set
+//! a minimum balance below which a holder account is reaped, like the
+//! existential deposit of the native currency
+//! Allowances can be increased/decreased instead of overwritten, may expire
+//! at a block number, and an allowance of TokenBalance::max_value() is
+//! unlimited and never decremented by transfer_from
+//! 3) Permit:
+//!     Allows a holder to sign an approval offline, that anyone can submit
+//! 4) Order book:
+//!     Orders placed on chain lock the offered tokens in escrow until they
+//!     are filled (fully or partially) or cancelled, the book is indexed by
+//!     trading pair and price
 use codec::{Codec, Decode, Encode};
-use frame_support::traits::Get;
+use frame_support::traits::{Currency, ExistenceRequirement, Get, ReservableCurrency};
 use frame_support::{
-    decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure, Parameter,
+    debug, decl_error, decl_event, decl_module, decl_storage,
+    dispatch::DispatchResult,
+    ensure,
+    storage::{
+        migration::{take_storage_value, StorageIterator},
+        unhashed, IterableStorageDoubleMap, StoragePrefixedMap,
+    },
+    transactional,
+    weights::{DispatchClass, FunctionOf, Pays, Weight},
+    Parameter,
 };
 use frame_system::{self as system, ensure_signed};
-use sp_arithmetic::traits::BaseArithmetic;
-use sp_runtime::traits::{
-    CheckedAdd, CheckedSub, IdentifyAccount, Member, One, StaticLookup, Verify,
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_arithmetic::{helpers_128bit::multiply_by_rational, traits::BaseArithmetic};
+use sp_runtime::{
+    traits::{
+        AccountIdConversion, Bounded, CheckedAdd, CheckedMul, CheckedSub, Hash, IdentifyAccount,
+        Member, One, Saturating, StaticLookup, Verify, Zero,
+    },
+    DispatchError, ModuleId,
 };
-use sp_std::{convert::TryInto, vec::Vec};
+use sp_std::{
+    convert::{TryFrom, TryInto},
+    vec::Vec,
+};
+
+/// Types necessary to enable using currency
+type BalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
 
 /// The module's configuration trait.
 pub trait Trait: frame_system::Trait + pallet_balances::Trait {
     /// The overarching event type.
     type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+    /// Currency type to reserve token creation deposits in
+    type Currency: ReservableCurrency<Self::AccountId>;
+    /// The deposit reserved from the creator of a token, this is set in lib.rs
+    type TokenDeposit: Get<BalanceOf<Self>>;
     /// Custom types for tokens
     type TokenBalance: Parameter + Member + Codec + Default + Copy + BaseArithmetic;
     type TokenId: Parameter + Member + Codec + Default + Copy + BaseArithmetic;
@@ -45,24 +93,199 @@ pub trait Trait: frame_system::Trait + pallet_balances::Trait {
     type Public: IdentifyAccount<AccountId = Self::AccountId>;
     type Signature: Verify<Signer = Self::Public> + Member + Decode + Encode;
     type MaxTransfers: Get<u8>;
+    /// Maximum lengths (in bytes) of the token metadata fields
+    type MaxNameLength: Get<u32>;
+    type MaxSymbolLength: Get<u32>;
+    type MaxUriLength: Get<u32>;
+    /// This is the max number of offers settled in a single settle_batch
+    type MaxBatchSize: Get<u32>;
+    /// This is the max number of offers and ring trades forgotten in a
+    /// single on_initialize, the others are forgotten in the next blocks
+    type MaxOffersPerBlock: Get<u32>;
+}
+
+/// Signed offer of the configured types, with an optional fill amount
+type SettlementOf<T> = (
+    SignedOffer<
+        <T as Trait>::Signature,
+        <T as frame_system::Trait>::AccountId,
+        <T as Trait>::TokenBalance,
+        <T as Trait>::TokenId,
+        <T as frame_system::Trait>::BlockNumber,
+    >,
+    Option<<T as Trait>::TokenBalance>,
+);
+
+/// Tag of the signed offers
+pub const OFFER_TAG: &[u8] = b"prc20/offer";
+/// Tag of the signed permits
+pub const PERMIT_TAG: &[u8] = b"prc20/permit";
+/// Tag of the signed ring trades
+pub const RING_TAG: &[u8] = b"prc20/ring";
+
+/// The PRC20 module id, used for deriving the order book escrow account ID.
+const MODULE_ID: ModuleId = ModuleId(*b"py/prc20");
+
+/// Order book prices are the requested amount per PRICE_UNIT of offered tokens
+pub const PRICE_UNIT: u128 = 1_000_000_000_000;
+
+/// Max number of holders returned by a top holders query
+pub const MAX_TOP_HOLDERS: u32 = 100;
+
+/// Length of the hash prefixing the keys of blake2_128_concat maps
+const BLAKE2_128_LEN: usize = 16;
+
+/// Identifier of the orders of the order book
+pub type OrderId = u64;
+
+/// Identifier of the hashed time-locked contracts
+pub type HtlcId = u64;
+
+/// Identifier of the balance snapshots of a token, the first one is 1
+pub type SnapshotId = u32;
+
+/// Metadata of a token, set at creation and updatable by the token owner
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct TokenMetadata {
+    pub name: Vec<u8>,
+    pub symbol: Vec<u8>,
+    pub decimals: u8,
+    pub uri: Option<Vec<u8>>,
+}
+
+/// Storage layout versions of the module
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Releases {
+    /// balances, allowances and frozen accounts keyed by tuples
+    V1,
+    /// balances, allowances and frozen accounts keyed by double maps, with
+    /// the AccountTokens index and the HolderCount of each token
+    V2,
+}
+
+impl Default for Releases {
+    fn default() -> Self {
+        Releases::V1
+    }
+}
+
+/// Asset exchanged in atomic swaps, either the native currency or a token
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Asset<TokenId> {
+    Native,
+    Token(TokenId),
+}
+
+impl<TokenId> Default for Asset<TokenId> {
+    fn default() -> Self {
+        Asset::Native
+    }
+}
+
+/// Fee paid by the maker of an offer to the account submitting the swap,
+/// partial fills pay the proportional part of it
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RelayerFee<TokenBalance, TokenId> {
+    pub asset: Asset<TokenId>,
+    pub amount: TokenBalance,
 }
 
 /// Offer struct used in atomic swaps, this is not signed
 #[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
 //#[cfg_attr(feature = "std", derive(Debug))]
-pub struct Offer<TokenBalance, TokenId> {
-    pub offer_token: TokenId,
+pub struct Offer<AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub offer_asset: Asset<TokenId>,
     pub offer_amount: TokenBalance,
-    pub requested_token: TokenId,
+    pub requested_asset: Asset<TokenId>,
     pub requested_amount: TokenBalance,
     pub nonce: u128,
+    pub partial_fill: bool,
+    pub expiry: BlockNumber,
+    /// the only account allowed to take the offer, anyone if None
+    pub taker: Option<AccountId>,
+    /// the fee paid to the account submitting the swap
+    pub relayer_fee: Option<RelayerFee<TokenBalance, TokenId>>,
 }
 
 /// Signed version of the offer struct, used in atomic swaps
 #[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
 //#[cfg_attr(feature = "std", derive(Debug))]
-pub struct SignedOffer<Signature, AccountId, TokenBalance, TokenId> {
-    pub offer: Offer<TokenBalance, TokenId>,
+pub struct SignedOffer<Signature, AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub offer: Offer<AccountId, TokenBalance, TokenId, BlockNumber>,
+    pub signature: Signature,
+    pub signer: AccountId,
+}
+
+/// Leg of a ring trade, `from` gives `amount` of `asset` to `to`
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RingLeg<AccountId, TokenBalance, TokenId> {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub asset: Asset<TokenId>,
+    pub amount: TokenBalance,
+}
+
+/// Trade between many parties settled atomically, every party signs
+/// the whole ring so that no leg can be settled without the others
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RingTrade<AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub legs: Vec<RingLeg<AccountId, TokenBalance, TokenId>>,
+    /// only tells apart otherwise identical trades
+    pub nonce: u128,
+    /// last block at which the trade can be settled
+    pub expiry: BlockNumber,
+}
+
+/// Signed version of the ring trade, with the signature of the sender
+/// of each leg, in the order of the legs
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+pub struct SignedRingTrade<Signature, AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub trade: RingTrade<AccountId, TokenBalance, TokenId, BlockNumber>,
+    pub signatures: Vec<Signature>,
+}
+
+/// Order of the on-chain order book, the part of the offered amount not
+/// filled yet is held in escrow
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Order<AccountId, TokenBalance, TokenId> {
+    pub maker: AccountId,
+    pub offer_token: TokenId,
+    pub offer_amount: TokenBalance,
+    pub requested_token: TokenId,
+    pub requested_amount: TokenBalance,
+    pub filled: TokenBalance,
+    pub price: u128,
+}
+
+/// Hashed time-locked contract, the locked amount is held in escrow until
+/// the recipient reveals the sha256 preimage of the hashlock, or until the
+/// sender takes it back once the timeout block has passed
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Htlc<AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub sender: AccountId,
+    pub recipient: AccountId,
+    pub asset: Asset<TokenId>,
+    pub amount: TokenBalance,
+    pub hashlock: [u8; 32],
+    pub timeout: BlockNumber,
+}
+
+/// Permit struct used in gasless approvals, this is not signed
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Permit<AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub token: TokenId,
+    pub spender: AccountId,
+    pub value: TokenBalance,
+    pub nonce: u128,
+    pub deadline: BlockNumber,
+}
+
+/// Signed version of the permit struct, submitted by anyone on behalf
+/// of the signer
+#[derive(Encode, Decode, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct SignedPermit<Signature, AccountId, TokenBalance, TokenId, BlockNumber> {
+    pub permit: Permit<AccountId, TokenBalance, TokenId, BlockNumber>,
     pub signature: Signature,
     pub signer: AccountId,
 }
@@ -90,6 +313,62 @@ decl_error! {
         /// too many multiTransfers
         /// based on the MaxTransfers u32 set in lib.rs
         LimitExceeded,
+        /// token name is longer than MaxNameLength
+        NameTooLong,
+        /// token symbol is longer than MaxSymbolLength
+        SymbolTooLong,
+        /// token uri is longer than MaxUriLength
+        UriTooLong,
+        /// token does not exist
+        UnknownToken,
+        /// sender is not the owner of the token
+        NotOwner,
+        /// got overflow while adding (used in mint)
+        Overflow,
+        /// the account is frozen for this token and can't send it
+        AccountFrozen,
+        /// all transfers of this token are paused
+        TokenPaused,
+        /// the token can only be destroyed once its whole supply is burned
+        SupplyNotBurned,
+        /// the transfer would leave the sender below the minimum balance
+        KeepAlive,
+        /// the resulting balance would be below the token's minimum balance
+        BelowMinimum,
+        /// the allowance has expired
+        AllowanceExpired,
+        /// the permit deadline has passed
+        PermitExpired,
+        /// the offered or filled amount is zero
+        ZeroOffer,
+        /// the offer can only be filled in full
+        PartialFillNotAllowed,
+        /// the fill is larger than what remains of the offer
+        FillExceedsOffer,
+        /// the offer has expired
+        OfferExpired,
+        /// the offer has already been filled completely
+        OfferAlreadyFilled,
+        /// the offer has been cancelled by its maker
+        CancelledOffer,
+        /// the offer is restricted to another taker
+        NotOfferTaker,
+        /// the order amounts are zero or the order trades a token for itself
+        InvalidOrder,
+        /// there is no order with this id
+        UnknownOrder,
+        /// only the maker of an order can cancel it
+        NotOrderMaker,
+        /// the ring trade has less than two legs or not one signature per leg
+        InvalidRing,
+        /// there is no htlc with this id
+        UnknownHtlc,
+        /// the preimage does not hash to the hashlock
+        InvalidPreimage,
+        /// the htlc timeout has passed, it can only be refunded
+        HtlcExpired,
+        /// the htlc can't be refunded before its timeout has passed
+        HtlcNotExpired,
     }
 }
 
@@ -100,14 +379,120 @@ decl_storage! {
         /// this stores the total supply of each token
         TotalSupply get(fn total_supply): map hasher(blake2_128_concat)
             T::TokenId => T::TokenBalance;
-        /// this stores balance maps for each token + addr
-        Balances get(fn balance_of): map hasher(blake2_128_concat)
-            (T::TokenId, T::AccountId) => T::TokenBalance;
-        /// this stores allowances (to enable transferFrom)
-        Allowance get(fn allowance_of): map hasher(blake2_128_concat)
-            (T::TokenId, T::AccountId, T::AccountId) => T::TokenBalance;
+        /// this stores the storage layout version, for runtime upgrade migrations
+        StorageVersion get(fn storage_version) build(|_| Releases::V2): Releases;
+        /// this stores balance maps for each token + addr,
+        /// the holders of a token can be iterated by prefix
+        Balances get(fn balance_of): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) T::AccountId => T::TokenBalance;
+        /// this stores the id of the last snapshot taken of each token, 0 if none
+        CurrentSnapshot get(fn current_snapshot): map hasher(blake2_128_concat)
+            T::TokenId => SnapshotId;
+        /// this stores the number of balance checkpoints of an account and
+        /// the snapshot of its last checkpoint
+        BalanceCheckpointCount get(fn balance_checkpoints): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) T::AccountId => (u32, SnapshotId);
+        /// this stores the balance checkpoints of an account by index, lowest
+        /// snapshot first, a checkpoint is the balance at a snapshot and is
+        /// only written once the balance changes after the snapshot
+        BalanceCheckpoints get(fn balance_checkpoint): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) (T::AccountId, u32)
+            => (SnapshotId, T::TokenBalance);
+        /// this stores the number of total supply checkpoints of a token and
+        /// the snapshot of its last checkpoint
+        SupplyCheckpointCount get(fn supply_checkpoints): map hasher(blake2_128_concat)
+            T::TokenId => (u32, SnapshotId);
+        /// this stores the total supply checkpoints of a token by index,
+        /// lowest snapshot first
+        SupplyCheckpoints get(fn supply_checkpoint): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) u32 => (SnapshotId, T::TokenBalance);
+        /// this stores the number of accounts holding each token
+        HolderCount get(fn holder_count): map hasher(blake2_128_concat)
+            T::TokenId => u32;
+        /// this stores the tokens held by each account, the key is the big
+        /// endian bytes of the token id, so iterating the prefix of an
+        /// account yields its tokens lowest id first
+        AccountTokens: double_map hasher(blake2_128_concat)
+            T::AccountId, hasher(identity) [u8; 16] => bool;
+        /// this stores the holders of each token ordered by balance, the key
+        /// starts with the big endian bytes of u128::max_value() - balance,
+        /// so iterating the prefix of a token yields the largest holders first
+        HoldersByBalance: double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(identity) ([u8; 16], T::AccountId) => bool;
+        /// this stores allowances (to enable transferFrom) of each token + owner
+        /// for each spender, the allowances of an owner can be iterated by prefix
+        Allowance get(fn allowance_of): double_map hasher(blake2_128_concat)
+            (T::TokenId, T::AccountId), hasher(blake2_128_concat) T::AccountId
+            => T::TokenBalance;
+        /// this stores the last block at which an allowance can be used,
+        /// allowances without an expiry never expire
+        AllowanceExpiry get(fn allowance_expiry): double_map hasher(blake2_128_concat)
+            (T::TokenId, T::AccountId), hasher(blake2_128_concat) T::AccountId
+            => Option<T::BlockNumber>;
+        /// this stores the number of spenders with an allowance from each
+        /// owner of a token, the owners of a token can be iterated by prefix
+        AllowanceOwners get(fn allowance_owners): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) T::AccountId => u32;
+        /// this stores the permit nonce of each account (replay protection)
+        PermitNonces get(fn permit_nonce): map hasher(blake2_128_concat)
+            T::AccountId => u128;
+        /// this stores the offered amount already filled for each offer
+        /// (keyed by offer hash), this is the replay protection of swaps
+        OfferFills get(fn offer_filled): map hasher(identity)
+            T::Hash => Option<T::TokenBalance>;
+        /// this stores the offers cancelled by their maker (keyed by offer hash)
+        CancelledOffers get(fn is_cancelled): map hasher(identity)
+            T::Hash => bool;
+        /// this stores the ring trades already settled (keyed by trade hash)
+        SettledRings get(fn is_ring_settled): map hasher(identity)
+            T::Hash => bool;
+        /// this stores the offers and ring trades to forget at each block,
+        /// once they have expired and can't be replayed anymore
+        OfferExpiries get(fn offer_expiries): map hasher(twox_64_concat)
+            T::BlockNumber => Vec<T::Hash>;
+        /// this stores the first block whose offers and ring trades are
+        /// not all forgotten yet, it is set by the first on_initialize
+        ExpiryCursor get(fn expiry_cursor): Option<T::BlockNumber>;
+        /// this stores the orders of the order book
+        Orders get(fn order): map hasher(twox_64_concat)
+            OrderId => Option<Order<T::AccountId, T::TokenBalance, T::TokenId>>;
+        /// this stores the id of the next order placed
+        NextOrderId get(fn next_order_id): OrderId;
+        /// this stores the ids of the orders of a pair (offer token,
+        /// requested token) at a price, the orders at a price can be
+        /// iterated by prefix
+        OrderBook: double_map hasher(blake2_128_concat)
+            (T::TokenId, T::TokenId, u128), hasher(twox_64_concat) OrderId => bool;
+        /// this stores the number of orders of a pair (offer token,
+        /// requested token) at each price with orders, the prices of a
+        /// pair can be iterated by prefix
+        PriceLevels get(fn orders_at_price): double_map hasher(blake2_128_concat)
+            (T::TokenId, T::TokenId), hasher(blake2_128_concat) u128 => u32;
+        /// this stores the pending hashed time-locked contracts
+        Htlcs get(fn htlc): map hasher(twox_64_concat)
+            HtlcId => Option<Htlc<T::AccountId, T::TokenBalance, T::TokenId, T::BlockNumber>>;
+        /// this stores the id of the next htlc
+        NextHtlcId get(fn next_htlc_id): HtlcId;
         /// this stores the total number of different tokens in the blockchain
         TokenCount get(fn token_count): T::TokenId;
+        /// this stores the metadata (name, symbol, decimals, uri) of each token
+        Metadata get(fn metadata_of): map hasher(blake2_128_concat)
+            T::TokenId => TokenMetadata;
+        /// this stores the owner (creator) of each token
+        Owner get(fn owner_of): map hasher(blake2_128_concat)
+            T::TokenId => Option<T::AccountId>;
+        /// this stores the accounts frozen by the token owner for each token
+        FrozenAccounts get(fn is_frozen): double_map hasher(blake2_128_concat)
+            T::TokenId, hasher(blake2_128_concat) T::AccountId => bool;
+        /// this stores the tokens paused by their owner
+        PausedTokens get(fn is_paused): map hasher(blake2_128_concat)
+            T::TokenId => bool;
+        /// this stores the depositor and the deposit reserved for each token
+        Deposits get(fn deposit_of): map hasher(blake2_128_concat)
+            T::TokenId => Option<(T::AccountId, BalanceOf<T>)>;
+        /// this stores the minimum balance an account must keep for each token
+        MinBalance get(fn min_balance): map hasher(blake2_128_concat)
+            T::TokenId => T::TokenBalance;
     }
 }
 
@@ -120,36 +505,241 @@ decl_module! {
         /// this is needed only if you are using events in your module
         fn deposit_event() = default;
 
+        /// Migrate the storage to the current layout after a runtime upgrade
+        fn on_runtime_upgrade() -> Weight {
+            let mut weight = T::DbWeight::get().reads(1);
+            if Self::storage_version() == Releases::V1 {
+                weight = weight.saturating_add(Self::migrate_to_v2());
+            }
+            weight
+        }
+
+        /// Run on every block initialize
+        fn on_initialize(n: T::BlockNumber) -> Weight {
+            Self::forget_expired(n)
+        }
+
         ///create a new token,
         /// passing totalSupply, (currently creator will receive total supply)
-        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        /// and the token metadata, the creator becomes the token owner
+        /// and has TokenDeposit reserved until the token is destroyed,
+        /// accounts holding less than min_balance of the token are reaped
+        #[weight = T::DbWeight::get().reads_writes(2, 8) + 70_000_000]
         fn create_token(origin,
-            #[compact] total_supply: T::TokenBalance
+            #[compact] total_supply: T::TokenBalance,
+            #[compact] min_balance: T::TokenBalance,
+            metadata: TokenMetadata
         ) -> DispatchResult {
             // ensure signed from the sender
             let sender = ensure_signed(origin)?;
-            // count the current token id
-            let current_id = Self::token_count();
-            // add one to the id using checked_add, if it errors out broadcast
-            // a MaxTokenLimitReached error
-            let next_id = match current_id.checked_add(&One::one()) {
-                Some(r) => r,
-                None => return Err(<Error<T>>::MaxTokenLimitReached.into()),
-            };
-            // Update the token count
-            <TokenCount<T>>::put(next_id);
-            // Add the currency id and total supply
-            <TotalSupply<T>>::insert(current_id, total_supply);
-            // in this example we send the total supply to the creator
-            <Balances<T>>::insert((current_id, sender.clone()), total_supply);
-            // Broadcast a NewToken event
+            Self::do_create_token(&sender, &sender, total_supply, min_balance, metadata)?;
+            Ok(())
+        }
+
+        /// update the metadata of a token, only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        fn set_metadata(origin,
+            #[compact] id: T::TokenId,
+            metadata: TokenMetadata
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may change the metadata
+            Self::ensure_owner(id, &sender)?;
+            // ensure the metadata fits in the configured bounds
+            Self::check_metadata(&metadata)?;
+            <Metadata<T>>::insert(id, metadata);
+            // broadcast a MetadataSet event
+            Self::deposit_event(RawEvent::MetadataSet(id, sender));
+            Ok(())
+        }
+
+        /// take a snapshot of the balances and the total supply of a token,
+        /// only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(2, 1) + 70_000_000]
+        fn snapshot(origin, #[compact] id: T::TokenId) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may take snapshots
+            Self::ensure_owner(id, &sender)?;
+            let snapshot_id = Self::current_snapshot(id)
+                .checked_add(1)
+                .ok_or(<Error<T>>::Overflow)?;
+            <CurrentSnapshot<T>>::insert(id, snapshot_id);
+            // broadcast a Snapshot event
+            Self::deposit_event(RawEvent::Snapshot(id, snapshot_id,
+                <system::Module<T>>::block_number()));
+            Ok(())
+        }
+
+        /// destroy a token whose whole supply has been burned, returning the
+        /// creation deposit, only callable by the token owner,
+        /// the allowances and frozen accounts of the token are removed
+        #[weight = T::DbWeight::get().reads_writes(4, 16) + 70_000_000]
+        fn destroy_token(origin,
+            #[compact] id: T::TokenId
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may destroy the token
+            Self::ensure_owner(id, &sender)?;
+            // the whole supply has to be burned first
+            ensure!(Self::total_supply(id).is_zero(), <Error<T>>::SupplyNotBurned);
+            // return the deposit to whoever paid it
+            let mut returned = Zero::zero();
+            if let Some((depositor, deposit)) = <Deposits<T>>::take(id) {
+                T::Currency::unreserve(&depositor, deposit);
+                returned = deposit;
+            }
+            // remove the token, its id is never reused
+            <TotalSupply<T>>::remove(id);
+            <Balances<T>>::remove(id, sender.clone());
+            <Owner<T>>::remove(id);
+            <Metadata<T>>::remove(id);
+            <PausedTokens<T>>::remove(id);
+            <MinBalance<T>>::remove(id);
+            <HolderCount<T>>::remove(id);
+            <CurrentSnapshot<T>>::remove(id);
+            <SupplyCheckpointCount<T>>::remove(id);
+            <SupplyCheckpoints<T>>::remove_prefix(id);
+            <BalanceCheckpointCount<T>>::remove_prefix(id);
+            <BalanceCheckpoints<T>>::remove_prefix(id);
+            <FrozenAccounts<T>>::remove_prefix(id);
+            // remove the allowances left, owner by owner
+            for (owner, _) in <AllowanceOwners<T>>::drain_prefix(id) {
+                <Allowance<T>>::remove_prefix((id, owner.clone()));
+                <AllowanceExpiry<T>>::remove_prefix((id, owner));
+            }
+            // broadcast a TokenDestroyed event
+            Self::deposit_event(RawEvent::TokenDestroyed(id, sender, returned));
+            Ok(())
+        }
+
+        /// mint new tokens to an account, only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(3, 2) + 70_000_000]
+        fn mint(origin,
+            to: <T::Lookup as StaticLookup>::Source,
+            #[compact] id: T::TokenId,
+            #[compact] amount: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let to = T::Lookup::lookup(to)?;
+            // only the token owner may mint
+            Self::ensure_owner(id, &sender)?;
+            Self::do_mint(id, &to, amount)
+        }
+
+        /// burn tokens from the owner's own balance,
+        /// only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(3, 2) + 70_000_000]
+        fn burn(origin,
+            #[compact] id: T::TokenId,
+            #[compact] amount: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may burn
+            Self::ensure_owner(id, &sender)?;
+            Self::do_burn(id, &sender, amount)
+        }
+
+        /// hand the ownership of a token to another account
+        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        fn transfer_ownership(origin,
+            #[compact] id: T::TokenId,
+            new_owner: <T::Lookup as StaticLookup>::Source
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let new_owner = T::Lookup::lookup(new_owner)?;
+            // only the token owner may hand over the ownership
+            Self::ensure_owner(id, &sender)?;
+            <Owner<T>>::insert(id, new_owner.clone());
+            // broadcast an OwnershipTransferred event
             Self::deposit_event(
-                RawEvent::NewToken(current_id, sender, total_supply));
+                RawEvent::OwnershipTransferred(id, sender, new_owner));
             Ok(())
         }
 
-        /// do transfers like erc20 ( TokenId, To, Amount)
+        /// give up the ownership of a token, after this the supply
+        /// and the metadata of the token can never change again,
+        /// the creation deposit stays reserved for the storage it keeps
         #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        fn renounce_ownership(origin,
+            #[compact] id: T::TokenId
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may renounce the ownership
+            Self::ensure_owner(id, &sender)?;
+            <Owner<T>>::remove(id);
+            // broadcast an OwnershipRenounced event
+            Self::deposit_event(RawEvent::OwnershipRenounced(id, sender));
+            Ok(())
+        }
+
+        /// freeze the balance of an account for a token, a frozen account
+        /// can't send the token, only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(2, 1) + 70_000_000]
+        fn freeze(origin,
+            #[compact] id: T::TokenId,
+            who: <T::Lookup as StaticLookup>::Source
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let who = T::Lookup::lookup(who)?;
+            // only the token owner may freeze accounts
+            Self::ensure_owner(id, &sender)?;
+            <FrozenAccounts<T>>::insert(id, who.clone(), true);
+            // broadcast a Frozen event
+            Self::deposit_event(RawEvent::Frozen(id, who));
+            Ok(())
+        }
+
+        /// thaw a frozen account for a token, only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(2, 1) + 70_000_000]
+        fn thaw(origin,
+            #[compact] id: T::TokenId,
+            who: <T::Lookup as StaticLookup>::Source
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let who = T::Lookup::lookup(who)?;
+            // only the token owner may thaw accounts
+            Self::ensure_owner(id, &sender)?;
+            <FrozenAccounts<T>>::remove(id, who.clone());
+            // broadcast a Thawed event
+            Self::deposit_event(RawEvent::Thawed(id, who));
+            Ok(())
+        }
+
+        /// pause all transfers of a token, only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(2, 1) + 70_000_000]
+        fn pause(origin,
+            #[compact] id: T::TokenId
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may pause the token
+            Self::ensure_owner(id, &sender)?;
+            <PausedTokens<T>>::insert(id, true);
+            // broadcast a Paused event
+            Self::deposit_event(RawEvent::Paused(id));
+            Ok(())
+        }
+
+        /// resume the transfers of a paused token,
+        /// only callable by the token owner
+        #[weight = T::DbWeight::get().reads_writes(2, 1) + 70_000_000]
+        fn unpause(origin,
+            #[compact] id: T::TokenId
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // only the token owner may unpause the token
+            Self::ensure_owner(id, &sender)?;
+            <PausedTokens<T>>::remove(id);
+            // broadcast an Unpaused event
+            Self::deposit_event(RawEvent::Unpaused(id));
+            Ok(())
+        }
+
+        /// do transfers like erc20 ( TokenId, To, Amount)
+        #[weight = T::DbWeight::get().reads_writes(10, 15) + 70_000_000]
         fn transfer(origin,
             to: <T::Lookup as StaticLookup>::Source,
             #[compact] id: T::TokenId,
@@ -158,40 +748,145 @@ decl_module! {
             let sender = ensure_signed(origin)?;
             // convert from lookup to T::AccountId
             let to = T::Lookup::lookup(to)?;
+            // ensure the token is not paused and the sender not frozen
+            Self::ensure_can_send(id, &sender)?;
             // do balance check
             let enough_balance = Self::check_enough_balance(id,
                 sender.clone(),
                 amount);
             ensure!(enough_balance, <Error<T>>::InsufficientBalance);
             // make the transfer
-            Self::make_transfer(id, sender, to, amount)
+            Self::make_transfer(id, sender, to, amount, ExistenceRequirement::AllowDeath)
         }
 
-        /// do approval like erc20 (TokenId, To, Amount)
-        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        /// same as transfer, but fails instead of reaping the sender account
+        /// if it would be left below the token's minimum balance
+        #[weight = T::DbWeight::get().reads_writes(10, 15) + 70_000_000]
+        fn transfer_keep_alive(origin,
+            to: <T::Lookup as StaticLookup>::Source,
+            #[compact] id: T::TokenId,
+            #[compact] amount: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let to = T::Lookup::lookup(to)?;
+            // ensure the token is not paused and the sender not frozen
+            Self::ensure_can_send(id, &sender)?;
+            // do balance check
+            let enough_balance = Self::check_enough_balance(id,
+                sender.clone(),
+                amount);
+            ensure!(enough_balance, <Error<T>>::InsufficientBalance);
+            // make the transfer
+            Self::make_transfer(id, sender, to, amount, ExistenceRequirement::KeepAlive)
+        }
+
+        /// do approval like erc20 (TokenId, To, Amount, Expiry)
+        /// the allowance can't be used after the expiry block (if any),
+        /// approving TokenBalance::max_value() grants an unlimited allowance
+        #[weight = T::DbWeight::get().reads_writes(3, 3) + 70_000_000]
         fn approve(origin,
             spender: <T::Lookup as StaticLookup>::Source,
             #[compact] id: T::TokenId,
-            #[compact] value: T::TokenBalance
+            #[compact] value: T::TokenBalance,
+            expiry: Option<T::BlockNumber>
         ) -> DispatchResult {
             let sender = ensure_signed(origin)?;
             // convert from lookup to T::AccountId
             let spender = T::Lookup::lookup(spender)?;
             // add to allowance
             //(here we don't mind someone setting 0 allowance)
-            <Allowance<T>>::insert((id,
-                sender.clone(),
-                spender.clone()),
-                value);
+            Self::set_allowance(id, &sender, &spender, value);
+            Self::set_allowance_expiry(id, &sender, &spender, expiry);
             // broadcast a Approval Event
             Self::deposit_event(
                 RawEvent::Approval(id, sender, spender, value));
             Ok(())
         }
 
+        /// increase an allowance by value, keeping its expiry
+        /// (avoids the approve front-running race), an expired allowance
+        /// stays expired, use approve to set a new expiry
+        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        fn increase_allowance(origin,
+            spender: <T::Lookup as StaticLookup>::Source,
+            #[compact] id: T::TokenId,
+            #[compact] value: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let spender = T::Lookup::lookup(spender)?;
+            // add to allowance, if there is an overflow error out
+            let allowance = Self::allowance_of((id, sender.clone()), spender.clone())
+                .checked_add(&value)
+                .ok_or(<Error<T>>::Overflow)?;
+            Self::set_allowance(id, &sender, &spender, allowance);
+            // broadcast a Approval Event
+            Self::deposit_event(
+                RawEvent::Approval(id, sender, spender, allowance));
+            Ok(())
+        }
+
+        /// decrease an allowance by value, keeping its expiry
+        /// (avoids the approve front-running race), the expiry is removed
+        /// with the allowance once it reaches zero
+        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        fn decrease_allowance(origin,
+            spender: <T::Lookup as StaticLookup>::Source,
+            #[compact] id: T::TokenId,
+            #[compact] value: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // convert from lookup to T::AccountId
+            let spender = T::Lookup::lookup(spender)?;
+            // subtract from allowance, if there is an underflow error out
+            let allowance = Self::allowance_of((id, sender.clone()), spender.clone())
+                .checked_sub(&value)
+                .ok_or(<Error<T>>::UnderFlow)?;
+            Self::set_allowance(id, &sender, &spender, allowance);
+            // broadcast a Approval Event
+            Self::deposit_event(
+                RawEvent::Approval(id, sender, spender, allowance));
+            Ok(())
+        }
+
+        /// approve a spender with an offline signed permit, the submitter
+        /// pays the fees so the holder needs no native currency
+        #[weight = T::DbWeight::get().reads_writes(1, 3) + 70_000_000]
+        fn permit(origin,
+            signed_permit: SignedPermit<T::Signature,
+                T::AccountId,
+                T::TokenBalance,
+                T::TokenId,
+                T::BlockNumber>
+        ) -> DispatchResult {
+            ensure_signed(origin)?;
+            // Ensure that the SignedPermit is signed correctly
+            ensure!(Self::verify_permit_signature(signed_permit.clone()).is_ok(),
+                <Error<T>>::InvalidSignature);
+            let permit = signed_permit.permit;
+            let owner = signed_permit.signer;
+            // ensure the permit is used before its deadline
+            ensure!(<system::Module<T>>::block_number() <= permit.deadline,
+                <Error<T>>::PermitExpired);
+            // ensure the permit nonce is correct (replay protection) or error
+            ensure!(Self::permit_nonce(&owner) == permit.nonce,
+                <Error<T>>::IncorrectNonce);
+            <PermitNonces<T>>::insert(&owner, permit.nonce + 1);
+            // set the allowance like approve
+            Self::set_allowance(permit.token, &owner, &permit.spender, permit.value);
+            Self::set_allowance_expiry(permit.token, &owner, &permit.spender, None);
+            // broadcast a Approval Event
+            Self::deposit_event(RawEvent::Approval(permit.token,
+                owner,
+                permit.spender,
+                permit.value));
+            Ok(())
+        }
+
         /// do transfer from
         ///(allows approver to spend token)(TokenId, From, To, Amount)
-        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        #[weight = T::DbWeight::get().reads_writes(13, 18) + 70_000_000]
         fn transfer_from(origin,
             from: <T::Lookup as StaticLookup>::Source,
             to: <T::Lookup as StaticLookup>::Source,
@@ -202,24 +897,38 @@ decl_module! {
             // convert from lookup to T::AccountId
             let from = T::Lookup::lookup(from)?;
             let to = T::Lookup::lookup(to)?;
+            // check the allowance has not expired
+            if let Some(expiry) = Self::allowance_expiry((id, from.clone()), sender.clone()) {
+                ensure!(<system::Module<T>>::block_number() <= expiry,
+                    <Error<T>>::AllowanceExpired);
+            }
             // check allowance
-            let allowance = Self::allowance_of((id,
-                from.clone(),
-                sender.clone()));
+            let allowance = Self::allowance_of((id, from.clone()), sender.clone());
             // check new allowance if transfer is made,
             // if there is an underflow, error with underflow error
-            let updated_allowance = match allowance.checked_sub(&value) {
-                Some(r) => r,
-                None => return Err(<Error<T>>::UnderFlow.into()),
+            // (an unlimited allowance is never decremented)
+            let updated_allowance = if allowance == T::TokenBalance::max_value() {
+                allowance
+            } else {
+                match allowance.checked_sub(&value) {
+                    Some(r) => r,
+                    None => return Err(<Error<T>>::UnderFlow.into()),
+                }
             };
+            // ensure the token is not paused and the owner not frozen
+            Self::ensure_can_send(id, &from)?;
             let enough_balance = Self::check_enough_balance(id,
                 from.clone(),
                 value);
             ensure!(enough_balance, <Error<T>>::InsufficientBalance);
             // make the transfer
-            Self::make_transfer(id, from.clone(), to.clone(), value)?;
+            Self::make_transfer(id,
+                from.clone(),
+                to.clone(),
+                value,
+                ExistenceRequirement::AllowDeath)?;
             // update the allowance
-            <Allowance<T>>::insert((id, from, sender), updated_allowance);
+            Self::set_allowance(id, &from, &sender, updated_allowance);
             Ok(())
         }
 
@@ -227,21 +936,224 @@ decl_module! {
         /// note that 0 token amounts are accepted
         /// since this allows a user to offer someone his tokens for free
         /// without paying any network fees
-        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        #[weight = T::DbWeight::get().reads_writes(32, 47) + 70_000_000]
         fn swap(origin,
             signed_offer:
-            SignedOffer<T::Signature,T::AccountId, T::TokenBalance, T::TokenId>
+            SignedOffer<T::Signature,
+                T::AccountId,
+                T::TokenBalance,
+                T::TokenId,
+                T::BlockNumber>
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // Ensure that the SignedOffer is signed correctly
+            ensure!(Self::verify_offer_signature(signed_offer.clone()).is_ok(),
+                <Error<T>>::InvalidSignature);
+            // Make the Swap
+            Self::make_swap(sender, signed_offer, None)
+        }
+
+        /// fill part of a partial_fill offer, the taker receives fill_amount
+        /// of the offered token and pays the proportional requested amount,
+        /// rounded up in favour of the maker
+        #[weight = T::DbWeight::get().reads_writes(32, 47) + 70_000_000]
+        fn swap_partial(origin,
+            signed_offer:
+            SignedOffer<T::Signature,
+                T::AccountId,
+                T::TokenBalance,
+                T::TokenId,
+                T::BlockNumber>,
+            #[compact] fill_amount: T::TokenBalance
         ) -> DispatchResult {
             let sender = ensure_signed(origin)?;
             // Ensure that the SignedOffer is signed correctly
             ensure!(Self::verify_offer_signature(signed_offer.clone()).is_ok(),
                 <Error<T>>::InvalidSignature);
             // Make the Swap
-            Self::make_swap(sender, signed_offer)
+            Self::make_swap(sender, signed_offer, Some(fill_amount))
+        }
+
+        /// cancel an offer handed out by the sender,
+        /// the remaining part of the offer can't be swapped anymore
+        #[weight = T::DbWeight::get().reads_writes(2, 2) + 70_000_000]
+        fn cancel_offer(origin,
+            offer: Offer<T::AccountId, T::TokenBalance, T::TokenId, T::BlockNumber>
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // an expired offer can't be swapped anyway
+            ensure!(<system::Module<T>>::block_number() <= offer.expiry,
+                <Error<T>>::OfferExpired);
+            let offer_hash = Self::offer_hash(&sender, &offer);
+            ensure!(!Self::is_cancelled(offer_hash), <Error<T>>::CancelledOffer);
+            // forget the cancellation once the offer has expired,
+            // unless it is already scheduled by a previous fill
+            if Self::offer_filled(offer_hash).is_none() {
+                <OfferExpiries<T>>::append(offer.expiry.saturating_add(One::one()), offer_hash);
+            }
+            <CancelledOffers<T>>::insert(offer_hash, true);
+            // broadcast offer cancelled event
+            Self::deposit_event(RawEvent::OfferCancelled(offer_hash, sender));
+            Ok(())
+        }
+
+        /// place an order on the order book, the offered tokens are
+        /// held in escrow until the order is filled or cancelled
+        #[weight = T::DbWeight::get().reads_writes(7, 6) + 70_000_000]
+        fn place_order(origin,
+            #[compact] offer_token: T::TokenId,
+            #[compact] offer_amount: T::TokenBalance,
+            #[compact] requested_token: T::TokenId,
+            #[compact] requested_amount: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            ensure!(!offer_amount.is_zero() && !requested_amount.is_zero()
+                && offer_token != requested_token, <Error<T>>::InvalidOrder);
+            ensure!(requested_token < Self::token_count(), <Error<T>>::UnknownToken);
+            // a part left in escrow must be redeemable, so an order
+            // can't be smaller than the minimum balance
+            ensure!(offer_amount >= Self::min_balance(offer_token), <Error<T>>::BelowMinimum);
+            // the maker must be able to receive the payment of a full fill
+            ensure!(requested_amount >= Self::min_balance(requested_token),
+                <Error<T>>::BelowMinimum);
+            Self::ensure_can_send(offer_token, &sender)?;
+            let price = Self::order_price(offer_amount, requested_amount)?;
+            // move the offered tokens to escrow
+            Self::do_transfer(offer_token, &sender, &Self::escrow_account(),
+                offer_amount, ExistenceRequirement::AllowDeath)?;
+            let order_id = Self::next_order_id();
+            <NextOrderId>::put(order_id.checked_add(1).ok_or(<Error<T>>::Overflow)?);
+            <Orders<T>>::insert(order_id, Order {
+                maker: sender.clone(),
+                offer_token,
+                offer_amount,
+                requested_token,
+                requested_amount,
+                filled: Zero::zero(),
+                price,
+            });
+            // add the order to the book
+            <OrderBook<T>>::insert((offer_token, requested_token, price), order_id, true);
+            <PriceLevels<T>>::mutate((offer_token, requested_token), price,
+                |count| *count = count.saturating_add(1));
+            // broadcast order placed event
+            Self::deposit_event(RawEvent::OrderPlaced(order_id, sender, offer_token,
+                offer_amount, requested_token, requested_amount));
+            Ok(())
+        }
+
+        /// fill part or all of an order, the taker receives fill_amount of
+        /// the offered token and pays the proportional requested amount,
+        /// rounded up in favour of the maker
+        #[weight = T::DbWeight::get().reads_writes(8, 8) + 70_000_000]
+        fn fill_order(origin,
+            #[compact] order_id: OrderId,
+            #[compact] fill_amount: T::TokenBalance
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::make_fill(sender, order_id, fill_amount)
+        }
+
+        /// cancel an order, the part not filled yet is returned to its maker
+        #[weight = T::DbWeight::get().reads_writes(5, 5) + 70_000_000]
+        fn cancel_order(origin, #[compact] order_id: OrderId) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let order = Self::order(order_id).ok_or(<Error<T>>::UnknownOrder)?;
+            ensure!(order.maker == sender, <Error<T>>::NotOrderMaker);
+            let remaining = order.offer_amount - order.filled;
+            // return the escrowed tokens
+            Self::do_transfer(order.offer_token, &Self::escrow_account(), &sender,
+                remaining, ExistenceRequirement::AllowDeath)?;
+            Self::remove_order(order_id, &order);
+            // broadcast order cancelled event
+            Self::deposit_event(RawEvent::OrderCancelled(order_id, sender, remaining));
+            Ok(())
+        }
+
+        /// lock an amount of an asset in escrow for a recipient, it can be
+        /// claimed with the sha256 preimage of the hashlock until the
+        /// timeout block, then refunded to the sender
+        #[weight = T::DbWeight::get().reads_writes(5, 4) + 70_000_000]
+        fn lock_htlc(origin,
+            recipient: <T::Lookup as StaticLookup>::Source,
+            asset: Asset<T::TokenId>,
+            #[compact] amount: T::TokenBalance,
+            hashlock: [u8; 32],
+            timeout: T::BlockNumber
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(now < timeout, <Error<T>>::HtlcExpired);
+            // the escrowed amount must be redeemable
+            if let Asset::Token(id) = asset {
+                ensure!(id < Self::token_count(), <Error<T>>::UnknownToken);
+                ensure!(!amount.is_zero() && amount >= Self::min_balance(id),
+                    <Error<T>>::BelowMinimum);
+            }
+            Self::ensure_can_send_asset(asset, &sender)?;
+            Self::ensure_asset_balance(asset, &sender, amount)?;
+            // move the locked amount to escrow
+            Self::move_asset(asset, &sender, &Self::escrow_account(), amount)?;
+            let htlc_id = Self::next_htlc_id();
+            <NextHtlcId>::put(htlc_id.checked_add(1).ok_or(<Error<T>>::Overflow)?);
+            <Htlcs<T>>::insert(htlc_id, Htlc {
+                sender: sender.clone(),
+                recipient: recipient.clone(),
+                asset,
+                amount,
+                hashlock,
+                timeout,
+            });
+            // broadcast htlc locked event
+            Self::deposit_event(RawEvent::HtlcLocked(htlc_id, sender, recipient,
+                asset, amount, hashlock, timeout));
+            Ok(())
+        }
+
+        /// claim a htlc for its recipient by revealing the preimage of its
+        /// hashlock, anyone knowing the preimage can submit the claim
+        #[weight = T::DbWeight::get().reads_writes(6, 3) + 70_000_000]
+        fn claim_htlc(origin, #[compact] htlc_id: HtlcId, preimage: Vec<u8>) -> DispatchResult {
+            ensure_signed(origin)?;
+            let htlc = Self::htlc(htlc_id).ok_or(<Error<T>>::UnknownHtlc)?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(now <= htlc.timeout, <Error<T>>::HtlcExpired);
+            ensure!(sp_io::hashing::sha2_256(&preimage) == htlc.hashlock,
+                <Error<T>>::InvalidPreimage);
+            Self::release_asset(htlc.asset, &htlc.recipient, htlc.amount)?;
+            <Htlcs<T>>::remove(htlc_id);
+            // the preimage lets the counterparty claim the other side of the swap
+            Self::deposit_event(RawEvent::HtlcClaimed(htlc_id, htlc.recipient, preimage));
+            Ok(())
+        }
+
+        /// refund a htlc to its sender once its timeout has passed
+        #[weight = T::DbWeight::get().reads_writes(6, 3) + 70_000_000]
+        fn refund_htlc(origin, #[compact] htlc_id: HtlcId) -> DispatchResult {
+            ensure_signed(origin)?;
+            let htlc = Self::htlc(htlc_id).ok_or(<Error<T>>::UnknownHtlc)?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(now > htlc.timeout, <Error<T>>::HtlcNotExpired);
+            Self::release_asset(htlc.asset, &htlc.sender, htlc.amount)?;
+            <Htlcs<T>>::remove(htlc_id);
+            // broadcast htlc refunded event
+            Self::deposit_event(RawEvent::HtlcRefunded(htlc_id, htlc.sender, htlc.amount));
+            Ok(())
         }
 
         /// multi transfer functionality for tokens
-        #[weight = T::DbWeight::get().reads_writes(1, 1) + 70_000_000]
+        #[weight = FunctionOf(
+            |args: (&T::TokenId, &Vec<TokenTransferDetails<T::AccountId, T::TokenBalance>>)| {
+                // Paused and Frozen, then a token transfer per leg
+                T::DbWeight::get().reads_writes(8, 15)
+                    .saturating_mul(args.1.len() as Weight)
+                    .saturating_add(T::DbWeight::get().reads(2))
+                    .saturating_add(70_000_000)
+            },
+            DispatchClass::Normal,
+            Pays::Yes,
+        )]
         fn multi_transfer(origin,
             #[compact] id: T::TokenId,
             td_vec: Vec<TokenTransferDetails<T::AccountId, T::TokenBalance>>
@@ -253,25 +1165,24 @@ decl_module! {
             //limit this to a certain amount of multiTransfers
             ensure!((num_transfers as u32) < (T::MaxTransfers::get() as u32),
                 <Error<T>>::LimitExceeded);
+            // ensure the token is not paused and the sender not frozen
+            Self::ensure_can_send(id, &sender)?;
             // build a status vector to push status of each transfer
             let mut status_vector: Vec<(T::AccountId, T::TokenBalance, bool)>=
                 Vec::new();
             // iterate
             for i in 0..num_transfers{
-                // Ensure enough balance (returns true or false)
-                //(if false we just don't make the transfer,
-                // and set the status to false in our event)
+                // Ensure enough balance and make the transfer
+                // (returns true or false, if false the transfer was not made
+                // and we set the status to false in our event)
                 let status = Self::check_enough_balance(id,
                     sender.clone(),
-                    td_vec[i].amount.clone());
-                // if enough balance make the transfer
-                if status{
-                    // should not fail, so we don't care about the return
-                    Self::make_transfer(id,
+                    td_vec[i].amount.clone())
+                    && Self::make_transfer(id,
                         sender.clone(),
                         td_vec[i].to.clone(),
-                        td_vec[i].amount.clone())?;
-                }
+                        td_vec[i].amount.clone(),
+                        ExistenceRequirement::AllowDeath).is_ok();
                 // push to status vector
                 status_vector.push((td_vec[i].to.clone(),
                     td_vec[i].amount,
@@ -281,580 +1192,3901 @@ decl_module! {
             Self::deposit_event(RawEvent::MultiTransfer(sender, status_vector));
             Ok(())
         }
-    }
-}
 
-decl_event!(
-    pub enum Event<T>
-    where
-        AccountId = <T as frame_system::Trait>::AccountId,
-        TokenId = <T as Trait>::TokenId,
-        TokenBalance = <T as Trait>::TokenBalance,
-    {
-        /// New token creation (tokenId, Creator AccountId, Amount)
-        NewToken(TokenId, AccountId, TokenBalance),
-        /// Simple token transfer (tokenId, Sender AccountId, Recipient AccountId, Amount)
-        Transfer(TokenId, AccountId, AccountId, TokenBalance),
-        /// Approval (tokenId, Sender AccountId, Spender AccountId, Amount)
-        Approval(TokenId, AccountId, AccountId, TokenBalance),
-        /// Token Swap (offerTokenId, offerAmount, requestedTokenId, requestedAmount, maker, taker)
-        Swap(
-            TokenId,
-            TokenBalance,
-            TokenId,
+        /// all-or-nothing multi transfer, either every transfer is made
+        /// or the whole call fails (use multi_transfer for best-effort)
+        #[weight = FunctionOf(
+            |args: (&T::TokenId, &Vec<TokenTransferDetails<T::AccountId, T::TokenBalance>>)| {
+                // Paused and Frozen, then a token transfer per leg
+                T::DbWeight::get().reads_writes(8, 15)
+                    .saturating_mul(args.1.len() as Weight)
+                    .saturating_add(T::DbWeight::get().reads(2))
+                    .saturating_add(70_000_000)
+            },
+            DispatchClass::Normal,
+            Pays::Yes,
+        )]
+        fn multi_transfer_all(origin,
+            #[compact] id: T::TokenId,
+            td_vec: Vec<TokenTransferDetails<T::AccountId, T::TokenBalance>>
+        ) -> DispatchResult{
+            // check if signed
+            let sender = ensure_signed(origin)?;
+            //limit this to a certain amount of multiTransfers
+            ensure!((td_vec.len() as u32) < (T::MaxTransfers::get() as u32),
+                <Error<T>>::LimitExceeded);
+            // ensure the token is not paused and the sender not frozen
+            Self::ensure_can_send(id, &sender)?;
+            // validate the total up front
+            let mut total: T::TokenBalance = Zero::zero();
+            for td in td_vec.iter() {
+                total = total.checked_add(&td.amount).ok_or(<Error<T>>::Overflow)?;
+            }
+            ensure!(Self::check_enough_balance(id, sender.clone(), total),
+                <Error<T>>::InsufficientBalance);
+            // make every transfer, or revert them all and report the failing one
+            Self::make_multi_transfer_all(id, sender.clone(), td_vec).map_err(|(index, e)| {
+                Self::deposit_event(RawEvent::BatchTransferFailed(id, sender, index));
+                e
+            })
+        }
+
+        /// settle many signed offers in one call, each with an optional
+        /// fill amount (None fills the whole remaining offer), either every
+        /// offer is settled (all_or_nothing) or each one that can be
+        #[weight = FunctionOf(
+            |args: (&Vec<SettlementOf<T>>, &bool)| {
+                // every offer is a swap, signature check included
+                T::DbWeight::get().reads_writes(32, 47)
+                    .saturating_add(70_000_000)
+                    .saturating_mul(args.0.len() as Weight)
+            },
+            DispatchClass::Normal,
+            Pays::Yes,
+        )]
+        fn settle_batch(origin,
+            settlements: Vec<SettlementOf<T>>,
+            all_or_nothing: bool
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            // limit the size of a batch
+            ensure!((settlements.len() as u32) <= T::MaxBatchSize::get(),
+                <Error<T>>::LimitExceeded);
+            if all_or_nothing {
+                // settle every offer, or revert them all and report the failing one
+                Self::make_settle_batch_all(sender.clone(), settlements).map_err(|(index, e)| {
+                    Self::deposit_event(RawEvent::BatchSettlementFailed(sender, index));
+                    e
+                })
+            } else {
+                // build a status vector to push status of each settlement
+                let mut status_vector: Vec<(T::Hash, bool)> = Vec::new();
+                for (signed_offer, fill_amount) in settlements {
+                    let offer_hash = Self::offer_hash(&signed_offer.signer, &signed_offer.offer);
+                    let settled = Self::verify_offer_signature(signed_offer.clone()).is_ok()
+                        && Self::make_swap(sender.clone(), signed_offer, fill_amount).is_ok();
+                    status_vector.push((offer_hash, settled));
+                }
+                // broadcast batch settled event
+                Self::deposit_event(RawEvent::BatchSettled(sender, status_vector));
+                Ok(())
+            }
+        }
+
+        /// settle a ring trade signed by the sender of every leg,
+        /// either every leg is settled or none is
+        #[weight = FunctionOf(
+            |args: (&SignedRingTrade<T::Signature,
+                T::AccountId,
+                T::TokenBalance,
+                T::TokenId,
+                T::BlockNumber>,)| {
+                // every leg is a transfer and a signature check
+                T::DbWeight::get().reads_writes(10, 15)
+                    .saturating_add(70_000_000)
+                    .saturating_mul(args.0.trade.legs.len() as Weight)
+                    .saturating_add(T::DbWeight::get().reads_writes(1, 2))
+            },
+            DispatchClass::Normal,
+            Pays::Yes,
+        )]
+        fn swap_ring(origin,
+            signed_trade: SignedRingTrade<T::Signature,
+                T::AccountId,
+                T::TokenBalance,
+                T::TokenId,
+                T::BlockNumber>
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let SignedRingTrade { trade, signatures } = signed_trade;
+            // a ring needs at least two legs, each one signed by its sender
+            ensure!(trade.legs.len() >= 2 && signatures.len() == trade.legs.len(),
+                <Error<T>>::InvalidRing);
+            ensure!((trade.legs.len() as u32) <= T::MaxBatchSize::get(),
+                <Error<T>>::LimitExceeded);
+            // ensure the trade has not expired
+            let now = <system::Module<T>>::block_number();
+            ensure!(now <= trade.expiry, <Error<T>>::OfferExpired);
+            // a settled trade can't be replayed
+            let trade_hash = T::Hashing::hash_of(&trade);
+            ensure!(!Self::is_ring_settled(trade_hash), <Error<T>>::OfferAlreadyFilled);
+            // ensure every sender signed the whole trade
+            let payload = Self::signing_payload(RING_TAG, &trade);
+            for (leg, signature) in trade.legs.iter().zip(signatures.iter()) {
+                ensure!(signature.verify(&payload[..], &leg.from),
+                    <Error<T>>::InvalidSignature);
+            }
+            // make every transfer, or revert them all
+            Self::make_ring_swap(&trade.legs)?;
+            // record the trade until it expires
+            <SettledRings<T>>::insert(trade_hash, true);
+            <OfferExpiries<T>>::append(trade.expiry.saturating_add(One::one()), trade_hash);
+            Self::deposit_event(RawEvent::RingSwap(trade_hash, sender, trade.legs.len() as u32));
+            Ok(())
+        }
+    }
+}
+
+decl_event!(
+    pub enum Event<T>
+    where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        TokenId = <T as Trait>::TokenId,
+        TokenBalance = <T as Trait>::TokenBalance,
+        Balance = BalanceOf<T>,
+        Hash = <T as frame_system::Trait>::Hash,
+        AssetId = Asset<<T as Trait>::TokenId>,
+        BlockNumber = <T as frame_system::Trait>::BlockNumber,
+    {
+        /// New token creation (tokenId, Creator AccountId, Amount)
+        NewToken(TokenId, AccountId, TokenBalance),
+        /// Simple token transfer (tokenId, Sender AccountId, Recipient AccountId, Amount)
+        Transfer(TokenId, AccountId, AccountId, TokenBalance),
+        /// Approval (tokenId, Sender AccountId, Spender AccountId, Amount)
+        Approval(TokenId, AccountId, AccountId, TokenBalance),
+        /// Swap (offerAsset, offerAmount, requestedAsset, requestedAmount, maker, taker)
+        Swap(
+            AssetId,
+            TokenBalance,
+            AssetId,
+            TokenBalance,
+            AccountId,
+            AccountId,
+        ),
+        /// Partial offer fill (offerHash, filledAmount, remainingAmount)
+        OfferFilled(Hash, TokenBalance, TokenBalance),
+        /// Offer cancelled (offerHash, maker AccountId)
+        OfferCancelled(Hash, AccountId),
+        /// Order placed (orderId, maker, offerTokenId, offerAmount, requestedTokenId, requestedAmount)
+        OrderPlaced(
+            OrderId,
+            AccountId,
+            TokenId,
+            TokenBalance,
+            TokenId,
             TokenBalance,
+        ),
+        /// Order filled (orderId, taker, filledAmount, paidAmount, remainingAmount)
+        OrderFilled(OrderId, AccountId, TokenBalance, TokenBalance, TokenBalance),
+        /// Order cancelled (orderId, maker, returnedAmount)
+        OrderCancelled(OrderId, AccountId, TokenBalance),
+        /// Relayer fee paid (offerHash, relayer AccountId, asset, amount)
+        RelayerFeePaid(Hash, AccountId, AssetId, TokenBalance),
+        /// Snapshot taken (tokenId, snapshotId, block number)
+        Snapshot(TokenId, SnapshotId, BlockNumber),
+        /// Htlc locked (htlcId, sender, recipient, asset, amount, hashlock, timeout)
+        HtlcLocked(
+            HtlcId,
             AccountId,
             AccountId,
+            AssetId,
+            TokenBalance,
+            [u8; 32],
+            BlockNumber,
         ),
+        /// Htlc claimed (htlcId, recipient, preimage)
+        HtlcClaimed(HtlcId, AccountId, Vec<u8>),
+        /// Htlc refunded (htlcId, sender, amount)
+        HtlcRefunded(HtlcId, AccountId, TokenBalance),
+        /// Ring trade settled (tradeHash, submitter AccountId, number of legs)
+        RingSwap(Hash, AccountId, u32),
+        /// Batch settled (submitter, Vec<(offerHash, Successful)>)
+        BatchSettled(AccountId, Vec<(Hash, bool)>),
+        /// All-or-nothing batch reverted
+        /// (submitter AccountId, index of the failing settlement)
+        BatchSettlementFailed(AccountId, u32),
         /// MultiTransfer (Vec<(Destination, Amount, Successful )>)
         MultiTransfer(AccountId, Vec<(AccountId, TokenBalance, bool)>),
+        /// All-or-nothing multi transfer reverted
+        /// (tokenId, sender AccountId, index of the failing transfer)
+        BatchTransferFailed(TokenId, AccountId, u32),
+        /// Token metadata set (tokenId, Owner AccountId)
+        MetadataSet(TokenId, AccountId),
+        /// Tokens minted (tokenId, Recipient AccountId, Amount)
+        Mint(TokenId, AccountId, TokenBalance),
+        /// Tokens burned (tokenId, Owner AccountId, Amount)
+        Burn(TokenId, AccountId, TokenBalance),
+        /// Token ownership transferred (tokenId, Old Owner AccountId, New Owner AccountId)
+        OwnershipTransferred(TokenId, AccountId, AccountId),
+        /// Token ownership renounced (tokenId, Old Owner AccountId)
+        OwnershipRenounced(TokenId, AccountId),
+        /// Account frozen for a token (tokenId, Frozen AccountId)
+        Frozen(TokenId, AccountId),
+        /// Account thawed for a token (tokenId, Thawed AccountId)
+        Thawed(TokenId, AccountId),
+        /// All transfers of a token paused (tokenId)
+        Paused(TokenId),
+        /// Transfers of a token resumed (tokenId)
+        Unpaused(TokenId),
+        /// Token destroyed (tokenId, Owner AccountId, Returned Deposit)
+        TokenDestroyed(TokenId, AccountId, Balance),
+        /// Balance below the minimum burned when reaping an account
+        /// (tokenId, Reaped AccountId, Amount)
+        DustLost(TokenId, AccountId, TokenBalance),
     }
 );
 
 impl<T: Trait> Module<T> {
-    fn check_enough_balance(id: T::TokenId, from: T::AccountId, amount: T::TokenBalance) -> bool {
-        let from_balance = Self::balance_of((id, from.clone()));
-        from_balance >= amount
-    }
-
-    ///transfer
-    fn make_transfer(
-        id: T::TokenId,
-        from: T::AccountId,
-        to: T::AccountId,
-        amount: T::TokenBalance,
-    ) -> DispatchResult {
-        // // get balance of account
-        let from_balance = Self::balance_of((id, from.clone()));
-        // modify sender and receiver balance map
-        // Reduce sender balance to (from_balance - amount)
-        <Balances<T>>::insert((id, from.clone()), from_balance - amount);
-        // Increase receiver balance to (balance + amount)
-        <Balances<T>>::mutate((id, to.clone()), |balance| *balance += amount);
-        // broadcast a transfer event
-        Self::deposit_event(RawEvent::Transfer(id, from.clone(), to, amount));
+    /// Ensures `who` is the owner of token `id`
+    fn ensure_owner(id: T::TokenId, who: &T::AccountId) -> DispatchResult {
+        ensure!(id < Self::token_count(), <Error<T>>::UnknownToken);
+        // a renounced token has no owner anymore
+        ensure!(
+            Self::owner_of(id).as_ref() == Some(who),
+            <Error<T>>::NotOwner
+        );
         Ok(())
     }
 
-    ///swap
-    fn make_swap(
-        sender: T::AccountId,
-        signed_offer: SignedOffer<T::Signature, T::AccountId, T::TokenBalance, T::TokenId>,
-    ) -> DispatchResult {
-        // Check from balance of offer creator
-        let offer_from_balance =
-            Self::balance_of((signed_offer.offer.offer_token, signed_offer.signer.clone()));
-        // ensure offerer has enough tokens or error
+    /// Ensures every metadata field is within its configured length
+    fn check_metadata(metadata: &TokenMetadata) -> DispatchResult {
         ensure!(
-            offer_from_balance >= signed_offer.offer.offer_amount,
-            <Error<T>>::InsufficientBalance
+            metadata.name.len() as u32 <= T::MaxNameLength::get(),
+            <Error<T>>::NameTooLong
         );
-        // Check from balance of requestor
-        let requested_from_balance =
-            Self::balance_of((signed_offer.offer.requested_token, sender.clone()));
-        // ensure requestor has enough tokens or error
         ensure!(
-            requested_from_balance >= signed_offer.offer.requested_amount,
-            <Error<T>>::InsufficientBalance
+            metadata.symbol.len() as u32 <= T::MaxSymbolLength::get(),
+            <Error<T>>::SymbolTooLong
         );
-        // get maker nonce
-        let maker_nonce: u128 =
-            TryInto::<u128>::try_into(<system::Module<T>>::account_nonce(&signed_offer.signer))
-                .map_err(|_| "error")?;
-        // ensure maker nonce is correct (replay protection) or error
+        if let Some(uri) = &metadata.uri {
+            ensure!(
+                uri.len() as u32 <= T::MaxUriLength::get(),
+                <Error<T>>::UriTooLong
+            );
+        }
+        Ok(())
+    }
+
+    /// Ensures token `id` is not paused and `who` is not frozen for it
+    pub fn ensure_can_send(id: T::TokenId, who: &T::AccountId) -> DispatchResult {
+        ensure!(!Self::is_paused(id), <Error<T>>::TokenPaused);
+        ensure!(!Self::is_frozen(id, who.clone()), <Error<T>>::AccountFrozen);
+        Ok(())
+    }
+
+    /// Creates a token owned by `owner`, the creation deposit is reserved
+    /// from `depositor` and the total supply is given to the owner
+    pub fn do_create_token(
+        depositor: &T::AccountId,
+        owner: &T::AccountId,
+        total_supply: T::TokenBalance,
+        min_balance: T::TokenBalance,
+        metadata: TokenMetadata,
+    ) -> Result<T::TokenId, DispatchError> {
+        // ensure the metadata fits in the configured bounds
+        Self::check_metadata(&metadata)?;
+        // the creator has to hold at least the minimum balance
         ensure!(
-            maker_nonce == signed_offer.offer.nonce,
-            <Error<T>>::IncorrectNonce
-        );
-        // modify sender and receiver balance map
-        // subtract offer token from maker
-        <Balances<T>>::insert(
-            (signed_offer.offer.offer_token, signed_offer.signer.clone()),
-            offer_from_balance - signed_offer.offer.offer_amount,
-        );
-        // add offer token to taker
-        <Balances<T>>::mutate(
-            (signed_offer.offer.offer_token, sender.clone()),
-            |balance| *balance += signed_offer.offer.offer_amount,
-        );
-        // subtract requested token from taker
-        <Balances<T>>::insert(
-            (signed_offer.offer.requested_token, sender.clone()),
-            requested_from_balance - signed_offer.offer.requested_amount,
+            total_supply.is_zero() || total_supply >= min_balance,
+            <Error<T>>::BelowMinimum
         );
-        // add requested token to maker
-        <Balances<T>>::mutate(
-            (
-                signed_offer.offer.requested_token,
-                signed_offer.signer.clone(),
-            ),
-            |balance| *balance += signed_offer.offer.requested_amount,
+        // count the current token id
+        let current_id = Self::token_count();
+        // add one to the id using checked_add, if it errors out broadcast
+        // a MaxTokenLimitReached error
+        let next_id = match current_id.checked_add(&One::one()) {
+            Some(r) => r,
+            None => return Err(<Error<T>>::MaxTokenLimitReached.into()),
+        };
+        // reserve the creation deposit, fails if the depositor can't pay it
+        let deposit = T::TokenDeposit::get();
+        T::Currency::reserve(depositor, deposit)?;
+        <Deposits<T>>::insert(current_id, (depositor.clone(), deposit));
+        // Update the token count
+        <TokenCount<T>>::put(next_id);
+        // Add the currency id and total supply
+        Self::set_total_supply(current_id, total_supply);
+        <MinBalance<T>>::insert(current_id, min_balance);
+        // in this example we send the total supply to the owner
+        Self::set_balance(current_id, owner, total_supply);
+        // the owner owns the token and its metadata
+        <Owner<T>>::insert(current_id, owner.clone());
+        <Metadata<T>>::insert(current_id, metadata);
+        // Broadcast a NewToken event
+        Self::deposit_event(RawEvent::NewToken(current_id, owner.clone(), total_supply));
+        Self::deposit_event(RawEvent::MetadataSet(current_id, owner.clone()));
+        Ok(current_id)
+    }
+
+    /// Mints new tokens to an account, increasing the total supply
+    pub fn do_mint(id: T::TokenId, to: &T::AccountId, amount: T::TokenBalance) -> DispatchResult {
+        // increase the total supply, erroring out on overflow
+        let total_supply = Self::total_supply(id)
+            .checked_add(&amount)
+            .ok_or(<Error<T>>::Overflow)?;
+        // the receiver balance can not overflow if the total supply didn't
+        let to_balance = Self::balance_of(id, to.clone()) + amount;
+        ensure!(
+            to_balance.is_zero() || to_balance >= Self::min_balance(id),
+            <Error<T>>::BelowMinimum
         );
-        // increment account nonce for replay protection
-        <system::Module<T>>::inc_account_nonce(&signed_offer.signer);
-        // broadcast deposit event
-        Self::deposit_event(RawEvent::Swap(
-            signed_offer.offer.offer_token,
-            signed_offer.offer.offer_amount,
-            signed_offer.offer.requested_token,
-            signed_offer.offer.requested_amount,
-            signed_offer.signer,
-            sender,
-        ));
+        Self::set_total_supply(id, total_supply);
+        Self::set_balance(id, to, to_balance);
+        // broadcast a Mint event
+        Self::deposit_event(RawEvent::Mint(id, to.clone(), amount));
+        Ok(())
+    }
 
+    /// Burns tokens from an account, reducing the total supply
+    pub fn do_burn(id: T::TokenId, from: &T::AccountId, amount: T::TokenBalance) -> DispatchResult {
+        // do balance check
+        let enough_balance = Self::check_enough_balance(id, from.clone(), amount);
+        ensure!(enough_balance, <Error<T>>::InsufficientBalance);
+        // the holder either burns everything or keeps the minimum balance
+        let remaining = Self::balance_of(id, from.clone()) - amount;
+        ensure!(
+            remaining.is_zero() || remaining >= Self::min_balance(id),
+            <Error<T>>::BelowMinimum
+        );
+        // reduce the holder balance and the total supply
+        Self::set_balance(id, from, remaining);
+        Self::set_total_supply(id, Self::total_supply(id) - amount);
+        // broadcast a Burn event
+        Self::deposit_event(RawEvent::Burn(id, from.clone(), amount));
         Ok(())
     }
 
-    /// Verifies that the signed offer is signed by the correct signer
-    fn verify_offer_signature(
-        signed_offer: SignedOffer<T::Signature, T::AccountId, T::TokenBalance, T::TokenId>,
-    ) -> Result<(), &'static str> {
-        match signed_offer
-            .signature
-            .verify(&signed_offer.offer.encode()[..], &signed_offer.signer)
-        {
-            true => Ok(()),
-            false => Err("signature is invalid"),
+    fn check_enough_balance(id: T::TokenId, from: T::AccountId, amount: T::TokenBalance) -> bool {
+        let from_balance = Self::balance_of(id, from.clone());
+        from_balance >= amount
+    }
+
+    /// Writes the balance of an account, zero balances are removed from storage,
+    /// this also keeps the AccountTokens index of the account up to date,
+    /// the HolderCount of the token and its holders ordered by balance
+    fn set_balance(id: T::TokenId, who: &T::AccountId, balance: T::TokenBalance) {
+        let held = <Balances<T>>::contains_key(id, who.clone());
+        // nothing to remove from an account without balance
+        if balance.is_zero() && !held {
+            return;
+        }
+        if held {
+            <HoldersByBalance<T>>::remove(
+                id,
+                Self::holder_key(Self::balance_of(id, who.clone()), who),
+            );
+        }
+        if !balance.is_zero() {
+            <HoldersByBalance<T>>::insert(id, Self::holder_key(balance, who), true);
+        }
+        // keep the balance of the last snapshot before changing it
+        let snapshot_id = Self::current_snapshot(id);
+        let (count, last) = Self::balance_checkpoints(id, who);
+        if last < snapshot_id {
+            <BalanceCheckpoints<T>>::insert(
+                id,
+                (who.clone(), count),
+                (snapshot_id, Self::balance_of(id, who.clone())),
+            );
+            <BalanceCheckpointCount<T>>::insert(id, who, (count + 1, snapshot_id));
+        }
+        if balance.is_zero() {
+            <Balances<T>>::remove(id, who.clone());
+            <HolderCount<T>>::mutate(id, |count| *count = count.saturating_sub(1));
+            <AccountTokens<T>>::remove(who, Self::token_key(id));
+        } else {
+            if !held {
+                <AccountTokens<T>>::insert(who, Self::token_key(id), true);
+                <HolderCount<T>>::mutate(id, |count| *count = count.saturating_add(1));
+            }
+            <Balances<T>>::insert(id, who.clone(), balance);
         }
     }
-}
 
-// tests for this module
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use frame_support::{
-        assert_noop, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
-        weights::Weight,
-    };
-    use sp_core::sr25519;
-    use sp_core::H256;
-    use sp_runtime::{
-        testing::Header,
-        traits::{BlakeTwo256, IdentityLookup},
-        Perbill,
-    };
-    use substrate_test_client::{self, AccountKeyring};
+    /// Key of a token in AccountTokens, ordered like the token ids
+    fn token_key(id: T::TokenId) -> [u8; 16] {
+        TryInto::<u128>::try_into(id)
+            .unwrap_or(u128::max_value())
+            .to_be_bytes()
+    }
 
-    mod prc20 {
-        pub use super::super::*;
+    /// Whether an account holds a token
+    pub fn holds_token(who: &T::AccountId, id: T::TokenId) -> bool {
+        <AccountTokens<T>>::contains_key(who, Self::token_key(id))
     }
 
-    impl_outer_origin! {
-        pub enum Origin for Test {}
+    /// Key of a holder in HoldersByBalance, the larger the balance the
+    /// lower the key, holders with the same balance are ordered by account
+    fn holder_key(balance: T::TokenBalance, who: &T::AccountId) -> ([u8; 16], T::AccountId) {
+        let balance = TryInto::<u128>::try_into(balance).unwrap_or(u128::max_value());
+        ((u128::max_value() - balance).to_be_bytes(), who.clone())
     }
 
-    impl_outer_event! {
-        pub enum Event for Test {
-            system<T>,
-            pallet_balances<T>,
-            prc20<T>,
+    /// Writes the total supply of a token, keeping the supply of the last
+    /// snapshot before changing it
+    fn set_total_supply(id: T::TokenId, total_supply: T::TokenBalance) {
+        let snapshot_id = Self::current_snapshot(id);
+        let (count, last) = Self::supply_checkpoints(id);
+        if last < snapshot_id {
+            <SupplyCheckpoints<T>>::insert(id, count, (snapshot_id, Self::total_supply(id)));
+            <SupplyCheckpointCount<T>>::insert(id, (count + 1, snapshot_id));
         }
+        <TotalSupply<T>>::insert(id, total_supply);
     }
 
-    /// The signature type used by accounts/transactions.
-    pub type Signature = sr25519::Signature;
-    /// An identifier for an account on this system.
-    pub type AccountId = <Signature as Verify>::Signer;
-    // For testing the module, we construct most of a mock runtime. This means
-    // first constructing a configuration type (`Test`) which `impl`s each of
-    // the configuration traits of modules we want to use.
-    #[derive(Clone, PartialEq, Eq, Debug)]
-    pub struct Test;
-
-    parameter_types! {
-        pub const BlockHashCount: u64 = 250;
-        pub const MaximumBlockWeight: Weight = 1024;
-        pub const MaximumBlockLength: u32 = 2 * 1024;
-        pub const AvailableBlockRatio: Perbill = Perbill::one();
-    }
-    impl frame_system::Trait for Test {
-        type Origin = Origin;
-        type BaseCallFilter = ();
-        type Index = u64;
-        type BlockNumber = u64;
-        type Call = ();
-        type Hash = H256;
-        type Hashing = BlakeTwo256;
-        type AccountId = AccountId;
-        type Lookup = IdentityLookup<Self::AccountId>;
-        type Header = Header;
-        type Event = Event;
-        type BlockHashCount = BlockHashCount;
-        type MaximumBlockWeight = MaximumBlockWeight;
-        type DbWeight = ();
-        type BlockExecutionWeight = ();
-        type ExtrinsicBaseWeight = ();
-        type AvailableBlockRatio = AvailableBlockRatio;
-        type MaximumBlockLength = MaximumBlockLength;
-        type MaximumExtrinsicWeight = MaximumBlockWeight;
-        type Version = ();
-        type ModuleToIndex = ();
-        type AccountData = pallet_balances::AccountData<u64>;
-        type OnNewAccount = ();
-        type OnKilledAccount = ();
+    /// Value at a snapshot from the `count` checkpoints read by `checkpoint`:
+    /// the value of the first checkpoint at or after the snapshot or, if it
+    /// has not changed since, the current value. The checkpoints are binary
+    /// searched, so about log2(count) of them are read
+    fn value_at(
+        count: u32,
+        checkpoint: impl Fn(u32) -> (SnapshotId, T::TokenBalance),
+        snapshot_id: SnapshotId,
+        current: T::TokenBalance,
+    ) -> T::TokenBalance {
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if checkpoint(middle).0 < snapshot_id {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low < count {
+            checkpoint(low).1
+        } else {
+            current
+        }
     }
 
-    parameter_types! {
-        pub const MaxTransfers: u8 = 100;
-    }
-    impl Trait for Test {
-        type Event = Event;
-        type TokenBalance = u128;
-        type TokenId = u128;
-        type Public = AccountId;
-        type Signature = Signature;
-        type MaxTransfers = MaxTransfers;
+    /// Balance of an account at a snapshot, None if the snapshot was not taken
+    pub fn balance_of_at(
+        id: T::TokenId,
+        who: T::AccountId,
+        snapshot_id: SnapshotId,
+    ) -> Option<T::TokenBalance> {
+        if snapshot_id == 0 || snapshot_id > Self::current_snapshot(id) {
+            return None;
+        }
+        let (count, _) = Self::balance_checkpoints(id, &who);
+        Some(Self::value_at(
+            count,
+            |index| Self::balance_checkpoint(id, (who.clone(), index)),
+            snapshot_id,
+            Self::balance_of(id, who.clone()),
+        ))
     }
 
-    parameter_types! {
-        pub const ExistentialDeposit: u64 = 1;
-        pub const TransferFee: u64 = 0;
-        pub const CreationFee: u64 = 0;
+    /// Total supply of a token at a snapshot, None if the snapshot was not taken
+    pub fn total_supply_at(id: T::TokenId, snapshot_id: SnapshotId) -> Option<T::TokenBalance> {
+        if snapshot_id == 0 || snapshot_id > Self::current_snapshot(id) {
+            return None;
+        }
+        let (count, _) = Self::supply_checkpoints(id);
+        Some(Self::value_at(
+            count,
+            |index| Self::supply_checkpoint(id, index),
+            snapshot_id,
+            Self::total_supply(id),
+        ))
     }
-    impl pallet_balances::Trait for Test {
-        type Balance = u64;
-        type Event = Event;
-        type ExistentialDeposit = ExistentialDeposit;
-        type AccountStore = System;
-        type DustRemoval = ();
+
+    /// Largest holders of a token with their balances, largest first,
+    /// at most `count` of them and never more than MAX_TOP_HOLDERS
+    pub fn top_holders(id: T::TokenId, count: u32) -> Vec<(T::AccountId, T::TokenBalance)> {
+        // the holders are read in order, only the ones returned are read
+        <HoldersByBalance<T>>::iter_prefix(id)
+            .take(count.min(MAX_TOP_HOLDERS) as usize)
+            .map(|((_, who), _)| {
+                let balance = Self::balance_of(id, who.clone());
+                (who, balance)
+            })
+            .collect()
     }
 
-    pub struct ExtBuilder;
-    type System = frame_system::Module<Test>;
-    // type Balances = pallet_balances::Module<Test>;
-    type PRC20 = Module<Test>;
+    /// Tokens held by an account with their balances, lowest token id
+    /// first, skipping the first `start` tokens and returning at most `limit`
+    pub fn tokens_of(
+        who: T::AccountId,
+        start: u32,
+        limit: u32,
+    ) -> Vec<(T::TokenId, T::TokenBalance)> {
+        // the tokens are read in order, the ones after the page are not read
+        <AccountTokens<T>>::iter_prefix(&who)
+            .skip(start as usize)
+            .take(limit as usize)
+            .filter_map(|(key, _)| T::TokenId::try_from(u128::from_be_bytes(key)).ok())
+            .map(|id| (id, Self::balance_of(id, who.clone())))
+            .collect()
+    }
 
-    impl ExtBuilder {
-        pub fn build() -> sp_io::TestExternalities {
-            let mut t = system::GenesisConfig::default()
-                .build_storage::<Test>()
-                .unwrap();
-            pallet_balances::GenesisConfig::<Test> { balances: vec![] }
-                .assimilate_storage(&mut t)
-                .unwrap();
-            t.into()
+    /// Writes an allowance, zero allowances are removed from storage
+    fn set_allowance(
+        id: T::TokenId,
+        owner: &T::AccountId,
+        spender: &T::AccountId,
+        value: T::TokenBalance,
+    ) {
+        let key = (id, owner.clone());
+        let exists = <Allowance<T>>::contains_key(&key, spender);
+        if value.is_zero() {
+            if exists {
+                <Allowance<T>>::remove(&key, spender);
+                <AllowanceExpiry<T>>::remove(&key, spender);
+                let owners = Self::allowance_owners(id, owner).saturating_sub(1);
+                if owners == 0 {
+                    <AllowanceOwners<T>>::remove(id, owner);
+                } else {
+                    <AllowanceOwners<T>>::insert(id, owner, owners);
+                }
+            }
+        } else {
+            if !exists {
+                <AllowanceOwners<T>>::mutate(id, owner, |count| *count = count.saturating_add(1));
+            }
+            <Allowance<T>>::insert(&key, spender, value);
         }
     }
 
-    #[test]
-    fn initial_token_count_is_zero() {
-        ExtBuilder::build().execute_with(|| {
-            // Make sure token count is 0
-            assert_eq!(PRC20::token_count(), 0);
-        });
+    /// Writes the expiry of an allowance, None means it never expires
+    fn set_allowance_expiry(
+        id: T::TokenId,
+        owner: &T::AccountId,
+        spender: &T::AccountId,
+        expiry: Option<T::BlockNumber>,
+    ) {
+        let key = (id, owner.clone());
+        match expiry {
+            Some(expiry) if <Allowance<T>>::contains_key(&key, spender) => {
+                <AllowanceExpiry<T>>::insert(&key, spender, expiry)
+            }
+            _ => <AllowanceExpiry<T>>::remove(&key, spender),
+        }
     }
 
-    #[test]
-    fn create_token_works() {
-        ExtBuilder::build().execute_with(|| {
-            let alice = AccountId::from(AccountKeyring::Alice);
-            // Create Token
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Make sure token count is now 1
-            assert_eq!(PRC20::token_count(), 1);
-            // Make sure the creator has 10000 tokens
-            assert_eq!(PRC20::balance_of((0, alice)), 10000);
-        });
+    /// Moves tokens between accounts without broadcasting an event.
+    /// Nothing is written unless the transfer is valid. A sender left below
+    /// the minimum balance is reaped (AllowDeath) and its dust burned,
+    /// or the transfer fails (KeepAlive). It reads up to 8 and writes up to
+    /// 15 storage items (balances, checkpoints and holder indices of both
+    /// accounts and the supply when dust is burned)
+    pub fn do_transfer(
+        id: T::TokenId,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: T::TokenBalance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        let min_balance = Self::min_balance(id);
+        // get balance of account
+        let from_balance = Self::balance_of(id, from.clone());
+        let remaining = from_balance
+            .checked_sub(&amount)
+            .ok_or(<Error<T>>::InsufficientBalance)?;
+        // would the sender be reaped by this transfer?
+        let would_die = remaining.is_zero() || remaining < min_balance;
+        ensure!(
+            !would_die || existence == ExistenceRequirement::AllowDeath,
+            <Error<T>>::KeepAlive
+        );
+        // a transfer to oneself doesn't change any balance
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = Self::balance_of(id, to.clone())
+            .checked_add(&amount)
+            .ok_or(<Error<T>>::Overflow)?;
+        ensure!(
+            to_balance.is_zero() || to_balance >= min_balance,
+            <Error<T>>::BelowMinimum
+        );
+        // modify sender and receiver balance map
+        if would_die && !remaining.is_zero() {
+            // the dust left below the minimum balance is burned
+            Self::set_total_supply(id, Self::total_supply(id) - remaining);
+            Self::set_balance(id, from, Zero::zero());
+            Self::deposit_event(RawEvent::DustLost(id, from.clone(), remaining));
+        } else {
+            Self::set_balance(id, from, remaining);
+        }
+        Self::set_balance(id, to, to_balance);
+        Ok(())
     }
 
-    #[test]
-    fn transfer_token_works() {
-        ExtBuilder::build().execute_with(|| {
-            let alice = AccountId::from(AccountKeyring::Alice);
-            let bob = AccountId::from(AccountKeyring::Bob);
-            // Create Token
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // the above create_token deposits the newly created tokens
-            // into alice's account ensure that the create_token succeeded by
+    ///transfer
+    pub fn make_transfer(
+        id: T::TokenId,
+        from: T::AccountId,
+        to: T::AccountId,
+        amount: T::TokenBalance,
+        existence: ExistenceRequirement,
+    ) -> DispatchResult {
+        // modify sender and receiver balance map
+        Self::do_transfer(id, &from, &to, amount, existence)?;
+        // broadcast a transfer event
+        Self::deposit_event(RawEvent::Transfer(id, from, to, amount));
+        Ok(())
+    }
+
+    /// makes every transfer of a multi transfer, if one of them fails
+    /// the transfers already made are reverted and the index of the
+    /// failing transfer is returned with its error
+    #[transactional]
+    fn make_multi_transfer_all(
+        id: T::TokenId,
+        sender: T::AccountId,
+        td_vec: Vec<TokenTransferDetails<T::AccountId, T::TokenBalance>>,
+    ) -> Result<(), (u32, DispatchError)> {
+        // build a status vector to push status of each transfer
+        let mut status_vector: Vec<(T::AccountId, T::TokenBalance, bool)> = Vec::new();
+        for (i, td) in td_vec.into_iter().enumerate() {
+            let transfer_result = Self::make_transfer(
+                id,
+                sender.clone(),
+                td.to.clone(),
+                td.amount,
+                ExistenceRequirement::AllowDeath,
+            );
+            if let Err(e) = transfer_result {
+                return Err((i as u32, e));
+            }
+            status_vector.push((td.to, td.amount, true));
+        }
+        // broadcast multi transfer event
+        Self::deposit_event(RawEvent::MultiTransfer(sender, status_vector));
+        Ok(())
+    }
+
+    /// settles every offer of a batch, if one of them fails
+    /// the offers already settled are reverted and the index of the
+    /// failing offer is returned with its error
+    #[transactional]
+    fn make_settle_batch_all(
+        sender: T::AccountId,
+        settlements: Vec<SettlementOf<T>>,
+    ) -> Result<(), (u32, DispatchError)> {
+        let mut status_vector: Vec<(T::Hash, bool)> = Vec::new();
+        for (i, (signed_offer, fill_amount)) in settlements.into_iter().enumerate() {
+            let offer_hash = Self::offer_hash(&signed_offer.signer, &signed_offer.offer);
+            if Self::verify_offer_signature(signed_offer.clone()).is_err() {
+                return Err((i as u32, <Error<T>>::InvalidSignature.into()));
+            }
+            if let Err(e) = Self::make_swap(sender.clone(), signed_offer, fill_amount) {
+                return Err((i as u32, e));
+            }
+            status_vector.push((offer_hash, true));
+        }
+        // broadcast batch settled event
+        Self::deposit_event(RawEvent::BatchSettled(sender, status_vector));
+        Ok(())
+    }
+
+    /// Forgets the offers and ring trades that expired up to block `now`,
+    /// oldest first. At most MaxOffersPerBlock are forgotten, the others
+    /// are left for the next blocks. An expired offer can't be swapped
+    /// anyway, so forgetting it late is harmless
+    fn forget_expired(now: T::BlockNumber) -> Weight {
+        let mut budget = T::MaxOffersPerBlock::get();
+        let mut at = Self::expiry_cursor().unwrap_or(now);
+        let (mut reads, mut writes): (Weight, Weight) = (1, 1);
+        // every block visited uses the budget, even without expiries
+        while at <= now && budget > 0 {
+            let mut expired = <OfferExpiries<T>>::take(at);
+            reads += 1;
+            writes += 1;
+            let left = if expired.len() > budget as usize {
+                expired.split_off(budget as usize)
+            } else {
+                Vec::new()
+            };
+            budget = budget.saturating_sub((expired.len() as u32).max(1));
+            for offer_hash in expired.iter() {
+                <OfferFills<T>>::remove(offer_hash);
+                <CancelledOffers<T>>::remove(offer_hash);
+                <SettledRings<T>>::remove(offer_hash);
+            }
+            writes += 3 * expired.len() as Weight;
+            if !left.is_empty() {
+                <OfferExpiries<T>>::insert(at, left);
+                break;
+            }
+            at += One::one();
+        }
+        <ExpiryCursor<T>>::put(at);
+        T::DbWeight::get().reads_writes(reads, writes)
+    }
+
+    /// Hash identifying an offer of a maker
+    fn offer_hash(
+        maker: &T::AccountId,
+        offer: &Offer<T::AccountId, T::TokenBalance, T::TokenId, T::BlockNumber>,
+    ) -> T::Hash {
+        T::Hashing::hash_of(&(maker, offer))
+    }
+
+    /// Requested amount owed to the maker once `filled` of the offered
+    /// amount has been taken, rounded up in favour of the maker
+    fn requested_for(
+        offer_amount: T::TokenBalance,
+        requested_amount: T::TokenBalance,
+        filled: T::TokenBalance,
+    ) -> Result<T::TokenBalance, DispatchError> {
+        if filled == offer_amount {
+            return Ok(requested_amount);
+        }
+        if filled.is_zero() {
+            return Ok(Zero::zero());
+        }
+        match filled.checked_mul(&requested_amount) {
+            Some(product) => {
+                let amount = product / offer_amount;
+                if amount * offer_amount == product {
+                    Ok(amount)
+                } else {
+                    Ok(amount + One::one())
+                }
+            }
+            None => {
+                // the product doesn't fit, compute it in 256 bits
+                let to_u128 = |x: T::TokenBalance| {
+                    TryInto::<u128>::try_into(x).map_err(|_| <Error<T>>::Overflow)
+                };
+                let (filled, requested_amount, offer_amount) = (
+                    to_u128(filled)?,
+                    to_u128(requested_amount)?,
+                    to_u128(offer_amount)?,
+                );
+                let amount = multiply_by_rational(filled, requested_amount, offer_amount)
+                    .map_err(|_| <Error<T>>::Overflow)?;
+                // the product is a multiple of offer_amount only if the part
+                // of offer_amount not shared with filled divides requested_amount
+                let amount = if requested_amount % (offer_amount / gcd(filled, offer_amount)) == 0 {
+                    amount
+                } else {
+                    amount.checked_add(1).ok_or(<Error<T>>::Overflow)?
+                };
+                T::TokenBalance::try_from(amount).map_err(|_| <Error<T>>::Overflow.into())
+            }
+        }
+    }
+
+    /// Moves the tuple keyed balances, allowances, allowance expiries and
+    /// frozen accounts to their double maps and rebuilds the AccountTokens
+    /// index. Each old key is decoded before it is removed, the entries that
+    /// fail to decode are left in place and counted in a warning. Every
+    /// entry is migrated in the upgrade block, the weight returned grows
+    /// with their number
+    fn migrate_to_v2() -> Weight {
+        // take the old entries before any double map key is written
+        // under the same prefix
+        let (balances, mut failed) =
+            Self::take_tuple_keyed::<(T::TokenId, T::AccountId), T::TokenBalance>(b"Balances");
+        let (allowances, failures) = Self::take_tuple_keyed::<
+            (T::TokenId, T::AccountId, T::AccountId),
+            T::TokenBalance,
+        >(b"Allowance");
+        failed = failed.saturating_add(failures);
+        let (expiries, failures) = Self::take_tuple_keyed::<
+            (T::TokenId, T::AccountId, T::AccountId),
+            T::BlockNumber,
+        >(b"AllowanceExpiry");
+        failed = failed.saturating_add(failures);
+        let (frozen, failures) =
+            Self::take_tuple_keyed::<(T::TokenId, T::AccountId), bool>(b"FrozenAccounts");
+        failed = failed.saturating_add(failures);
+        let migrated =
+            (balances.len() + allowances.len() + expiries.len() + frozen.len()) as Weight;
+        // drop the index written by the first AccountTokens layout and its
+        // flag, the index is rebuilt from the balances below
+        <AccountTokens<T>>::remove_all();
+        take_storage_value::<bool>(b"PRC20", b"AccountTokensIndexed", &[]);
+        for ((id, who), balance) in balances {
+            Self::set_balance(id, &who, balance);
+        }
+        for ((id, owner, spender), value) in allowances {
+            Self::set_allowance(id, &owner, &spender, value);
+        }
+        // the expiries are written once their allowance is
+        for ((id, owner, spender), expiry) in expiries {
+            Self::set_allowance_expiry(id, &owner, &spender, Some(expiry));
+        }
+        for ((id, who), is_frozen) in frozen {
+            <FrozenAccounts<T>>::insert(id, who, is_frozen);
+        }
+        if failed > 0 {
+            debug::warn!(
+                "prc20: {} entries failed to decode in migrate_to_v2",
+                failed
+            );
+        }
+        <StorageVersion>::put(Releases::V2);
+        T::DbWeight::get().reads_writes(2 + 4 * migrated + failed as Weight, 3 + 4 * migrated)
+    }
+
+    /// Takes the entries of a map of this module keyed by a tuple hashed
+    /// with blake2_128_concat, removing each key that decodes. Returns the
+    /// decoded entries and the number of keys that failed to decode, which
+    /// are left in place
+    fn take_tuple_keyed<K: Decode, V: Decode>(item: &[u8]) -> (Vec<(K, V)>, u32) {
+        let prefix = [
+            sp_io::hashing::twox_128(b"PRC20"),
+            sp_io::hashing::twox_128(item),
+        ]
+        .concat();
+        let mut failed: u32 = 0;
+        let entries = StorageIterator::<V>::new(b"PRC20", item)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|(key, value)| {
+                // the key follows its hash
+                match K::decode(&mut key.get(BLAKE2_128_LEN..).unwrap_or_default()) {
+                    Ok(decoded) => {
+                        unhashed::kill(&[&prefix[..], &key[..]].concat());
+                        Some((decoded, value))
+                    }
+                    Err(_) => {
+                        failed = failed.saturating_add(1);
+                        None
+                    }
+                }
+            })
+            .collect();
+        (entries, failed)
+    }
+
+    /// Allowance the spender can still use, zero once it has expired
+    pub fn usable_allowance(
+        id: T::TokenId,
+        owner: T::AccountId,
+        spender: T::AccountId,
+    ) -> T::TokenBalance {
+        match Self::allowance_expiry((id, owner.clone()), spender.clone()) {
+            Some(expiry) if <system::Module<T>>::block_number() > expiry => Zero::zero(),
+            _ => Self::allowance_of((id, owner), spender),
+        }
+    }
+
+    /// Metadata of a token, None if the token does not exist
+    pub fn token_metadata(id: T::TokenId) -> Option<TokenMetadata> {
+        if id < Self::token_count() {
+            Some(Self::metadata_of(id))
+        } else {
+            None
+        }
+    }
+
+    /// The account ID holding the escrowed tokens of the order book and the htlcs.
+    pub fn escrow_account() -> T::AccountId {
+        MODULE_ID.into_account()
+    }
+
+    /// Price of an order, the requested amount per PRICE_UNIT offered
+    fn order_price(
+        offer_amount: T::TokenBalance,
+        requested_amount: T::TokenBalance,
+    ) -> Result<u128, DispatchError> {
+        let to_u128 =
+            |x: T::TokenBalance| TryInto::<u128>::try_into(x).map_err(|_| <Error<T>>::Overflow);
+        multiply_by_rational(
+            to_u128(requested_amount)?,
+            PRICE_UNIT,
+            to_u128(offer_amount)?,
+        )
+        .map_err(|_| <Error<T>>::Overflow.into())
+    }
+
+    /// Removes an order from storage and from the book of its pair
+    fn remove_order(order_id: OrderId, order: &Order<T::AccountId, T::TokenBalance, T::TokenId>) {
+        let pair = (order.offer_token, order.requested_token);
+        <OrderBook<T>>::remove(
+            (order.offer_token, order.requested_token, order.price),
+            order_id,
+        );
+        let count = Self::orders_at_price(pair, order.price).saturating_sub(1);
+        if count == 0 {
+            <PriceLevels<T>>::remove(pair, order.price);
+        } else {
+            <PriceLevels<T>>::insert(pair, order.price, count);
+        }
+        <Orders<T>>::remove(order_id);
+    }
+
+    /// Prices with orders of a pair (offer token, requested token), lowest
+    /// price first, this reads every price of the pair
+    pub fn price_levels(pair: (T::TokenId, T::TokenId)) -> Vec<u128> {
+        let mut prices: Vec<u128> = <PriceLevels<T>>::iter_prefix(pair)
+            .map(|(price, _)| price)
+            .collect();
+        prices.sort();
+        prices
+    }
+
+    /// Ids of the orders of a pair at a price, oldest order first,
+    /// this reads every order at the price
+    pub fn orders_at(level: (T::TokenId, T::TokenId, u128)) -> Vec<OrderId> {
+        let mut ids: Vec<OrderId> = <OrderBook<T>>::iter_prefix(level)
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// fills an order, if the payment of the taker fails
+    /// the release of the escrowed tokens is reverted as well
+    #[transactional]
+    fn make_fill(
+        taker: T::AccountId,
+        order_id: OrderId,
+        fill_amount: T::TokenBalance,
+    ) -> DispatchResult {
+        let mut order = Self::order(order_id).ok_or(<Error<T>>::UnknownOrder)?;
+        ensure!(!fill_amount.is_zero(), <Error<T>>::InvalidOrder);
+        // the escrowed tokens can't be released for a frozen maker
+        Self::ensure_can_send(order.offer_token, &order.maker)?;
+        let remaining = order.offer_amount - order.filled;
+        ensure!(fill_amount <= remaining, <Error<T>>::FillExceedsOffer);
+        let new_filled = order.filled + fill_amount;
+        // the part left in escrow must stay redeemable
+        let left = remaining - fill_amount;
+        ensure!(
+            left.is_zero() || left >= Self::min_balance(order.offer_token),
+            <Error<T>>::BelowMinimum
+        );
+        Self::ensure_can_send(order.requested_token, &taker)?;
+        // the requested amount paid for this fill
+        let paid = Self::requested_for(order.offer_amount, order.requested_amount, new_filled)?
+            - Self::requested_for(order.offer_amount, order.requested_amount, order.filled)?;
+        // release the escrowed tokens to the taker
+        Self::do_transfer(
+            order.offer_token,
+            &Self::escrow_account(),
+            &taker,
+            fill_amount,
+            ExistenceRequirement::AllowDeath,
+        )?;
+        // pay the maker
+        Self::do_transfer(
+            order.requested_token,
+            &taker,
+            &order.maker,
+            paid,
+            ExistenceRequirement::AllowDeath,
+        )?;
+        if left.is_zero() {
+            Self::remove_order(order_id, &order);
+        } else {
+            order.filled = new_filled;
+            <Orders<T>>::insert(order_id, order);
+        }
+        // broadcast order filled event
+        Self::deposit_event(RawEvent::OrderFilled(
+            order_id,
+            taker,
+            fill_amount,
+            paid,
+            left,
+        ));
+        Ok(())
+    }
+
+    /// Relayer fee owed by the maker once `filled` of the offered
+    /// amount has been taken, rounded down in favour of the maker
+    fn relayer_fee_for(
+        fee_amount: T::TokenBalance,
+        offer_amount: T::TokenBalance,
+        filled: T::TokenBalance,
+    ) -> Result<T::TokenBalance, DispatchError> {
+        if filled == offer_amount {
+            return Ok(fee_amount);
+        }
+        let to_u128 =
+            |x: T::TokenBalance| TryInto::<u128>::try_into(x).map_err(|_| <Error<T>>::Overflow);
+        let fee = multiply_by_rational(
+            to_u128(fee_amount)?,
+            to_u128(filled)?,
+            to_u128(offer_amount)?,
+        )
+        .map_err(|_| <Error<T>>::Overflow)?;
+        T::TokenBalance::try_from(fee).map_err(|_| <Error<T>>::Overflow.into())
+    }
+
+    /// Converts a swap amount to the native currency balance type
+    fn native_amount(amount: T::TokenBalance) -> Result<BalanceOf<T>, DispatchError> {
+        let amount = TryInto::<u128>::try_into(amount).map_err(|_| <Error<T>>::Overflow)?;
+        BalanceOf::<T>::try_from(amount).map_err(|_| <Error<T>>::Overflow.into())
+    }
+
+    /// Ensure an asset can be sent by an account,
+    /// the native currency is never paused or frozen by this module
+    fn ensure_can_send_asset(asset: Asset<T::TokenId>, who: &T::AccountId) -> DispatchResult {
+        match asset {
+            Asset::Native => Ok(()),
+            Asset::Token(id) => Self::ensure_can_send(id, who),
+        }
+    }
+
+    /// Ensure an account has enough of an asset
+    fn ensure_asset_balance(
+        asset: Asset<T::TokenId>,
+        who: &T::AccountId,
+        amount: T::TokenBalance,
+    ) -> DispatchResult {
+        let enough = match asset {
+            Asset::Native => T::Currency::free_balance(who) >= Self::native_amount(amount)?,
+            Asset::Token(id) => Self::check_enough_balance(id, who.clone(), amount),
+        };
+        ensure!(enough, <Error<T>>::InsufficientBalance);
+        Ok(())
+    }
+
+    /// Moves an asset between accounts, native funds go through the
+    /// currency and must keep the sender account alive
+    fn move_asset(
+        asset: Asset<T::TokenId>,
+        from: &T::AccountId,
+        to: &T::AccountId,
+        amount: T::TokenBalance,
+    ) -> DispatchResult {
+        match asset {
+            Asset::Native => T::Currency::transfer(
+                from,
+                to,
+                Self::native_amount(amount)?,
+                ExistenceRequirement::KeepAlive,
+            ),
+            Asset::Token(id) => {
+                Self::do_transfer(id, from, to, amount, ExistenceRequirement::AllowDeath)
+            }
+        }
+    }
+
+    ///swap, fills the whole remaining offer if no fill amount is given
+    #[transactional]
+    fn make_swap(
+        sender: T::AccountId,
+        signed_offer: SignedOffer<
+            T::Signature,
+            T::AccountId,
+            T::TokenBalance,
+            T::TokenId,
+            T::BlockNumber,
+        >,
+        fill_amount: Option<T::TokenBalance>,
+    ) -> DispatchResult {
+        let offer = signed_offer.offer;
+        let maker = signed_offer.signer;
+        // ensure something is offered
+        ensure!(!offer.offer_amount.is_zero(), <Error<T>>::ZeroOffer);
+        // ensure both assets can be sent by their senders
+        Self::ensure_can_send_asset(offer.offer_asset, &maker)?;
+        Self::ensure_can_send_asset(offer.requested_asset, &sender)?;
+        // ensure the offer is not restricted to another taker
+        if let Some(taker) = &offer.taker {
+            ensure!(taker == &sender, <Error<T>>::NotOfferTaker);
+        }
+        // ensure the offer has not expired
+        let now = <system::Module<T>>::block_number();
+        ensure!(now <= offer.expiry, <Error<T>>::OfferExpired);
+        // work out how much of the offer is filled by this swap
+        let offer_hash = Self::offer_hash(&maker, &offer);
+        // ensure the maker has not cancelled the offer
+        ensure!(!Self::is_cancelled(offer_hash), <Error<T>>::CancelledOffer);
+        let previous_fill = Self::offer_filled(offer_hash);
+        let filled = previous_fill.unwrap_or_else(Zero::zero);
+        let remaining = offer.offer_amount - filled;
+        // a completed offer can't be replayed
+        ensure!(
+            previous_fill.is_none() || !remaining.is_zero(),
+            <Error<T>>::OfferAlreadyFilled
+        );
+        let fill = fill_amount.unwrap_or(remaining);
+        // a fill of nothing would only mark the offer as taken
+        ensure!(!fill.is_zero(), <Error<T>>::ZeroOffer);
+        ensure!(
+            fill == offer.offer_amount || offer.partial_fill,
+            <Error<T>>::PartialFillNotAllowed
+        );
+        ensure!(fill <= remaining, <Error<T>>::FillExceedsOffer);
+        let new_filled = filled + fill;
+        // the requested amount paid for this fill
+        let paid = Self::requested_for(offer.offer_amount, offer.requested_amount, new_filled)?
+            - Self::requested_for(offer.offer_amount, offer.requested_amount, filled)?;
+        // ensure offerer has enough of the offered asset or error
+        Self::ensure_asset_balance(offer.offer_asset, &maker, fill)?;
+        // ensure requestor has enough of the requested asset or error
+        Self::ensure_asset_balance(offer.requested_asset, &sender, paid)?;
+        // if the second transfer fails the first one is reverted as well
+        // move offered asset from maker to taker
+        Self::move_asset(offer.offer_asset, &maker, &sender, fill)?;
+        // move requested asset from taker to maker
+        Self::move_asset(offer.requested_asset, &sender, &maker, paid)?;
+        // pay the relayer fee to the submitter
+        if let Some(relayer_fee) = offer.relayer_fee {
+            let fee = Self::relayer_fee_for(relayer_fee.amount, offer.offer_amount, new_filled)?
+                - Self::relayer_fee_for(relayer_fee.amount, offer.offer_amount, filled)?;
+            if !fee.is_zero() {
+                Self::ensure_can_send_asset(relayer_fee.asset, &maker)?;
+                Self::move_asset(relayer_fee.asset, &maker, &sender, fee)?;
+                Self::deposit_event(RawEvent::RelayerFeePaid(
+                    offer_hash,
+                    sender.clone(),
+                    relayer_fee.asset,
+                    fee,
+                ));
+            }
+        }
+        // record the fill (replay protection), the record is kept
+        // until the offer expires
+        if previous_fill.is_none() {
+            <OfferExpiries<T>>::append(offer.expiry.saturating_add(One::one()), offer_hash);
+        }
+        <OfferFills<T>>::insert(offer_hash, new_filled);
+        // broadcast deposit event
+        Self::deposit_event(RawEvent::Swap(
+            offer.offer_asset,
+            fill,
+            offer.requested_asset,
+            paid,
+            maker,
+            sender,
+        ));
+        if offer.partial_fill {
+            Self::deposit_event(RawEvent::OfferFilled(
+                offer_hash,
+                fill,
+                offer.offer_amount - new_filled,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Moves an asset out of escrow, the escrow account may be emptied,
+    /// a paused token or a frozen escrow can't be released like a transfer
+    fn release_asset(
+        asset: Asset<T::TokenId>,
+        to: &T::AccountId,
+        amount: T::TokenBalance,
+    ) -> DispatchResult {
+        Self::ensure_can_send_asset(asset, &Self::escrow_account())?;
+        match asset {
+            Asset::Native => T::Currency::transfer(
+                &Self::escrow_account(),
+                to,
+                Self::native_amount(amount)?,
+                ExistenceRequirement::AllowDeath,
+            ),
+            Asset::Token(id) => Self::do_transfer(
+                id,
+                &Self::escrow_account(),
+                to,
+                amount,
+                ExistenceRequirement::AllowDeath,
+            ),
+        }
+    }
+
+    /// Moves the asset of each leg of a ring trade, in order
+    #[transactional]
+    fn make_ring_swap(
+        legs: &[RingLeg<T::AccountId, T::TokenBalance, T::TokenId>],
+    ) -> DispatchResult {
+        for leg in legs.iter() {
+            Self::ensure_can_send_asset(leg.asset, &leg.from)?;
+            Self::ensure_asset_balance(leg.asset, &leg.from, leg.amount)?;
+            Self::move_asset(leg.asset, &leg.from, &leg.to, leg.amount)?;
+        }
+        Ok(())
+    }
+
+    /// Verifies that the signed permit is signed by the correct signer
+    fn verify_permit_signature(
+        signed_permit: SignedPermit<
+            T::Signature,
+            T::AccountId,
+            T::TokenBalance,
+            T::TokenId,
+            T::BlockNumber,
+        >,
+    ) -> Result<(), &'static str> {
+        match Self::verify_signed(
+            PERMIT_TAG,
+            &signed_permit.permit,
+            &signed_permit.signature,
+            &signed_permit.signer,
+        ) {
+            true => Ok(()),
+            false => Err("signature is invalid"),
+        }
+    }
+
+    /// Verifies that the signed offer is signed by the correct signer
+    fn verify_offer_signature(
+        signed_offer: SignedOffer<
+            T::Signature,
+            T::AccountId,
+            T::TokenBalance,
+            T::TokenId,
+            T::BlockNumber,
+        >,
+    ) -> Result<(), &'static str> {
+        match Self::verify_signed(
+            OFFER_TAG,
+            &signed_offer.offer,
+            &signed_offer.signature,
+            &signed_offer.signer,
+        ) {
+            true => Ok(()),
+            false => Err("signature is invalid"),
+        }
+    }
+
+    /// The bytes signed offline for a message of this module, see
+    /// `signing::signing_payload`
+    pub fn signing_payload<M: Encode>(tag: &[u8], message: &M) -> Vec<u8> {
+        signing::signing_payload::<T, M>(tag, message)
+    }
+
+    /// Verifies a signature of a message over its signing payload
+    fn verify_signed<M: Encode>(
+        tag: &[u8],
+        message: &M,
+        signature: &T::Signature,
+        signer: &T::AccountId,
+    ) -> bool {
+        signature.verify(&Self::signing_payload(tag, message)[..], signer)
+    }
+}
+
+/// Greatest common divisor of two numbers
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{
+        assert_noop, assert_ok, impl_outer_event, impl_outer_origin, parameter_types,
+        traits::{OnInitialize, OnRuntimeUpgrade},
+        weights::Weight,
+    };
+    use sp_core::sr25519;
+    use sp_core::H256;
+    use sp_runtime::{
+        testing::Header,
+        traits::{BlakeTwo256, IdentityLookup},
+        Perbill,
+    };
+    use substrate_test_client::{self, AccountKeyring};
+
+    mod prc20 {
+        pub use super::super::*;
+    }
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    impl_outer_event! {
+        pub enum Event for Test {
+            system<T>,
+            pallet_balances<T>,
+            prc20<T>,
+        }
+    }
+
+    /// The signature type used by accounts/transactions.
+    pub type Signature = sr25519::Signature;
+    /// An identifier for an account on this system.
+    pub type AccountId = <Signature as Verify>::Signer;
+    // For testing the module, we construct most of a mock runtime. This means
+    // first constructing a configuration type (`Test`) which `impl`s each of
+    // the configuration traits of modules we want to use.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub struct Test;
+
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::one();
+    }
+    impl frame_system::Trait for Test {
+        type Origin = Origin;
+        type BaseCallFilter = ();
+        type Index = u64;
+        type BlockNumber = u64;
+        type Call = ();
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = AccountId;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = Event;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type DbWeight = ();
+        type BlockExecutionWeight = ();
+        type ExtrinsicBaseWeight = ();
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type MaximumBlockLength = MaximumBlockLength;
+        type MaximumExtrinsicWeight = MaximumBlockWeight;
+        type Version = ();
+        type ModuleToIndex = ();
+        type AccountData = pallet_balances::AccountData<u64>;
+        type OnNewAccount = ();
+        type OnKilledAccount = ();
+    }
+
+    parameter_types! {
+        pub const MaxTransfers: u8 = 100;
+        pub const TokenDeposit: u64 = 10;
+        pub const MaxNameLength: u32 = 32;
+        pub const MaxSymbolLength: u32 = 8;
+        pub const MaxUriLength: u32 = 128;
+        pub const MaxBatchSize: u32 = 10;
+        pub const MaxOffersPerBlock: u32 = 3;
+    }
+    impl Trait for Test {
+        type Event = Event;
+        type Currency = pallet_balances::Module<Self>;
+        type TokenDeposit = TokenDeposit;
+        type TokenBalance = u128;
+        type TokenId = u128;
+        type Public = AccountId;
+        type Signature = Signature;
+        type MaxTransfers = MaxTransfers;
+        type MaxNameLength = MaxNameLength;
+        type MaxSymbolLength = MaxSymbolLength;
+        type MaxUriLength = MaxUriLength;
+        type MaxBatchSize = MaxBatchSize;
+        type MaxOffersPerBlock = MaxOffersPerBlock;
+    }
+
+    parameter_types! {
+        pub const ExistentialDeposit: u64 = 1;
+        pub const TransferFee: u64 = 0;
+        pub const CreationFee: u64 = 0;
+    }
+    impl pallet_balances::Trait for Test {
+        type Balance = u64;
+        type Event = Event;
+        type ExistentialDeposit = ExistentialDeposit;
+        type AccountStore = System;
+        type DustRemoval = ();
+    }
+
+    pub struct ExtBuilder;
+    type System = frame_system::Module<Test>;
+    // type Balances = pallet_balances::Module<Test>;
+    type NativeBalances = pallet_balances::Module<Test>;
+    type PRC20 = Module<Test>;
+
+    impl ExtBuilder {
+        pub fn build() -> sp_io::TestExternalities {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+
+            let mut t = system::GenesisConfig::default()
+                .build_storage::<Test>()
+                .unwrap();
+            pallet_balances::GenesisConfig::<Test> {
+                balances: vec![(alice, 1000), (bob, 1000)],
+            }
+            .assimilate_storage(&mut t)
+            .unwrap();
+            t.into()
+        }
+    }
+
+    #[test]
+    fn initial_token_count_is_zero() {
+        ExtBuilder::build().execute_with(|| {
+            // Make sure token count is 0
+            assert_eq!(PRC20::token_count(), 0);
+        });
+    }
+
+    #[test]
+    fn create_token_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            // Create Token
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Make sure token count is now 1
+            assert_eq!(PRC20::token_count(), 1);
+            // Make sure the creator has 10000 tokens
+            assert_eq!(PRC20::balance_of(0, alice), 10000);
+        });
+    }
+
+    #[test]
+    fn transfer_token_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // Create Token
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // the above create_token deposits the newly created tokens
+            // into alice's account ensure that the create_token succeeded by
             // checking Alice's balance for 10000
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 10000);
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
             // Make sure reciever has 0 tokens
-            assert_eq!(PRC20::balance_of((0, bob.clone())), 0);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 0);
             // make sure transfer goes ok and transfer
             assert_ok!(PRC20::transfer(Origin::signed(alice), bob.clone(), 0, 10));
             // make sure reciever balance is 10
-            assert_eq!(PRC20::balance_of((0, bob)), 10);
+            assert_eq!(PRC20::balance_of(0, bob), 10);
+        });
+    }
+
+    #[test]
+    fn transfer_fails_with_no_balance() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // Create Token
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Make sure the creator has 10000 tokens
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
+            // should fail
+            assert_noop!(
+                PRC20::transfer(Origin::signed(bob), alice, 0, 10),
+                Error::<Test>::InsufficientBalance
+            );
+        });
+    }
+
+    #[test]
+    fn approve_token_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // Make sure token count is 0
+            assert_eq!(PRC20::token_count(), 0);
+            // Create Token
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Make sure token count is now 1
+            assert_eq!(PRC20::token_count(), 1);
+            // Make sure the creator has 10000 tokens
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
+
+            // Maybe make sure initial approval is 0
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 0);
+            // Approve account 1 to use 10 tokens from account 0
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                10,
+                None
+            ));
+            // Maybe make sure current approval is 10
+            assert_eq!(PRC20::allowance_of((0, alice), bob), 10);
+        });
+    }
+
+    #[test]
+    fn transfer_from_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // Make sure token count is 0
+            assert_eq!(PRC20::token_count(), 0);
+            // Create Token
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Make sure token count is now 1
+            assert_eq!(PRC20::token_count(), 1);
+            // Make sure the creator has 10000 tokens
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
+            // Maybe make sure initial approval is 0
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 0);
+            // Approve account 1 to use 10 tokens from account 0
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                10,
+                None
+            ));
+            // Maybe make sure current approval is 10
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 10);
+            // Now lets use transfer_from
+            assert_ok!(PRC20::transfer_from(
+                Origin::signed(bob.clone()),
+                alice,
+                bob.clone(),
+                0,
+                10
+            ));
+            // Make sure balance is updated correctly
+            assert_eq!(PRC20::balance_of(0, bob), 10);
+        });
+    }
+
+    #[test]
+    fn swap_works() {
+        ExtBuilder::build().execute_with(|| {
+            // get account id for alice and bob
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // get account keyring for Bob
+            let bob_keyring = AccountKeyring::Bob;
+
+            // Alice creates token 0
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob creates token 1
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+
+            // Now bob creates an offer struct
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            // bob signs this using bob_keyring to create a signed_offer
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            // make sure swap is ok
+            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), signed_offer));
+            // Bob has 50 token 0
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 50);
+            // Bob has 9900 token 1
+            assert_eq!(PRC20::balance_of(1, bob), 9900);
+            // Alice has 9950 token 0
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 9950);
+            // Alice has 100 token 1
+            assert_eq!(PRC20::balance_of(1, alice), 100);
+        });
+    }
+
+    #[test]
+    fn swap_fails_not_enough_balance() {
+        ExtBuilder::build().execute_with(|| {
+            // get account id for alice and bob
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // get account keyring for Bob
+            let bob_keyring = AccountKeyring::Bob;
+            // Alice creates token 0
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob creates token 1
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+
+            // Now bob creates an offer struct
+            // (invalid since Bob owns token 1 and not 0)
+            let offer = Offer {
+                offer_asset: Asset::Token(0),
+                offer_amount: 100,
+                requested_asset: Asset::Token(1),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            // bob signs this using bob_keyring to create a signed_offer
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            // make sure swap fails
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), signed_offer),
+                Error::<Test>::InsufficientBalance
+            );
+            // Bob has 0 token 0
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 0);
+            // Bob has 9900 token 1
+            assert_eq!(PRC20::balance_of(1, bob), 10000);
+            // Alice has 9950 token 0
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
+            // Alice has 100 token 1
+            assert_eq!(PRC20::balance_of(1, alice), 0);
+        });
+    }
+
+    #[test]
+    fn swap_fails_if_expired() {
+        ExtBuilder::build().execute_with(|| {
+            // get account id for alice and bob
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // get account keyring for Bob
+            let bob_keyring = AccountKeyring::Bob;
+            // Alice creates token 0
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob creates token 1
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Now bob creates an offer struct expiring at block 10
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            // bob signs this using bob_keyring to create a signed_offer
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            // make sure swap fails after the expiry
+            System::set_block_number(11);
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), signed_offer),
+                Error::<Test>::OfferExpired
+            );
+            // Bob has 10000 token 1
+            assert_eq!(PRC20::balance_of(1, bob.clone()), 10000);
+            // Alice has 10000 token 0
+            assert_eq!(PRC20::balance_of(0, alice), 10000);
+        });
+    }
+
+    #[test]
+    fn independent_offers_and_no_replay() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob publishes two offers at once, told apart by their nonce
+            let sign = |nonce: u128| {
+                let offer = Offer {
+                    offer_asset: Asset::Token(1),
+                    offer_amount: 100,
+                    requested_asset: Asset::Token(0),
+                    requested_amount: 50,
+                    nonce,
+                    partial_fill: false,
+                    expiry: 10,
+                    taker: None,
+                    relayer_fee: None,
+                };
+                SignedOffer {
+                    offer: offer.clone(),
+                    signer: bob.clone(),
+                    signature: Signature::from(
+                        bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                    ),
+                }
+            };
+            let first_offer = sign(7);
+            let second_offer = sign(8);
+            // an unrelated transaction of Bob doesn't break his offers
+            System::inc_account_nonce(&bob);
+            assert_ok!(PRC20::swap(
+                Origin::signed(alice.clone()),
+                first_offer.clone()
+            ));
+            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), second_offer));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 200);
+            // the first offer can't be replayed
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), first_offer.clone()),
+                Error::<Test>::OfferAlreadyFilled
+            );
+            // the fill record is forgotten once the offer has expired
+            let offer_hash = PRC20::offer_hash(&bob, &first_offer.offer);
+            PRC20::on_initialize(11);
+            assert_eq!(PRC20::offer_filled(offer_hash), None);
+            System::set_block_number(11);
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice), first_offer),
+                Error::<Test>::OfferExpired
+            );
+        });
+    }
+
+    #[test]
+    fn multi_transfer_works() {
+        ExtBuilder::build().execute_with(|| {
+            // get account id for alice and bob
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            // Alice creates token 0
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // create first transfer details struct
+            let first_transfer = TokenTransferDetails {
+                amount: 5,
+                to: bob.clone(),
+            };
+            // create second transfer details struct
+            let second_transfer = TokenTransferDetails {
+                amount: 5,
+                to: charlie.clone(),
+            };
+            // Create a vector of these transfer details struct
+            let transfer_vec = vec![first_transfer, second_transfer];
+            // do a multi transfer from alice's account
+            assert_ok!(PRC20::multi_transfer(
+                Origin::signed(alice.clone()),
+                0,
+                transfer_vec
+            ));
+            // Bob has 5 token 0
+            assert_eq!(PRC20::balance_of(0, bob), 5);
+            // Charlie has 5 token 0
+            assert_eq!(PRC20::balance_of(0, charlie), 5);
+            // Alice has 9990 token 0
+            assert_eq!(PRC20::balance_of(0, alice), 9990);
+        });
+    }
+
+    #[test]
+    fn multi_transfer_fails_sequentially() {
+        // this basically means if the user does not have enough
+        // balances for all transfers, the first transfers in the vec
+        // have priority and go through
+        ExtBuilder::build().execute_with(|| {
+            // get account id for alice and bob
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            // Alice creates token 0
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // create first transfer details struct
+            let first_transfer = TokenTransferDetails {
+                amount: 10000,
+                to: bob.clone(),
+            };
+            // create second transfer details struct
+            let second_transfer = TokenTransferDetails {
+                amount: 5,
+                to: charlie.clone(),
+            };
+            // Create a vector of these transfer details struct
+            let transfer_vec = vec![first_transfer, second_transfer];
+
+            let _partial_transfer_result =
+                PRC20::multi_transfer(Origin::signed(alice.clone()), 0, transfer_vec);
+            // Bob has 10000 token 0
+            assert_eq!(PRC20::balance_of(0, bob), 10000);
+            // Alice has 0 token 0
+            assert_eq!(PRC20::balance_of(0, alice), 0);
+            // Charlie has 0 token 0 since the second transfer
+            // in the vector should fail
+            assert_eq!(PRC20::balance_of(0, charlie), 0);
+        });
+    }
+
+    #[test]
+    fn create_token_stores_metadata_and_owner() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let metadata = TokenMetadata {
+                name: b"Parrot Dollar".to_vec(),
+                symbol: b"PUSD".to_vec(),
+                decimals: 6,
+                uri: Some(b"https://www.oax.org/".to_vec()),
+            };
+            // Create Token with metadata
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                metadata.clone()
+            ));
+            // Make sure the metadata and owner are stored
+            assert_eq!(PRC20::metadata_of(0), metadata);
+            assert_eq!(PRC20::owner_of(0), Some(alice));
+        });
+    }
+
+    #[test]
+    fn create_token_fails_with_long_metadata() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            // symbol is longer than MaxSymbolLength
+            let metadata = TokenMetadata {
+                name: b"Parrot Dollar".to_vec(),
+                symbol: b"PARROTUSD".to_vec(),
+                decimals: 6,
+                uri: None,
+            };
+            assert_noop!(
+                PRC20::create_token(Origin::signed(alice), 10000, 0, metadata),
+                Error::<Test>::SymbolTooLong
+            );
+        });
+    }
+
+    #[test]
+    fn set_metadata_only_by_owner() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            let metadata = TokenMetadata {
+                name: b"Parrot".to_vec(),
+                symbol: b"PRT".to_vec(),
+                decimals: 18,
+                uri: None,
+            };
+            // Bob is not the owner
+            assert_noop!(
+                PRC20::set_metadata(Origin::signed(bob), 0, metadata.clone()),
+                Error::<Test>::NotOwner
+            );
+            // Unknown token
+            assert_noop!(
+                PRC20::set_metadata(Origin::signed(alice.clone()), 1, metadata.clone()),
+                Error::<Test>::UnknownToken
+            );
+            // Alice is the owner
+            assert_ok!(PRC20::set_metadata(
+                Origin::signed(alice),
+                0,
+                metadata.clone()
+            ));
+            assert_eq!(PRC20::metadata_of(0), metadata);
+        });
+    }
+
+    #[test]
+    fn mint_and_burn_work() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice mints 500 tokens to Bob
+            assert_ok!(PRC20::mint(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                500
+            ));
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 500);
+            assert_eq!(PRC20::total_supply(0), 10500);
+            // Bob is not the owner and can't mint
+            assert_noop!(
+                PRC20::mint(Origin::signed(bob.clone()), bob.clone(), 0, 500),
+                Error::<Test>::NotOwner
+            );
+            // Alice burns 1000 of her tokens
+            assert_ok!(PRC20::burn(Origin::signed(alice.clone()), 0, 1000));
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 9000);
+            assert_eq!(PRC20::total_supply(0), 9500);
+            // Alice can't burn more than she owns
+            assert_noop!(
+                PRC20::burn(Origin::signed(alice), 0, 9001),
+                Error::<Test>::InsufficientBalance
+            );
+        });
+    }
+
+    #[test]
+    fn mint_fails_on_overflow() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                u128::max_value(),
+                0,
+                TokenMetadata::default()
+            ));
+            assert_noop!(
+                PRC20::mint(Origin::signed(alice.clone()), alice, 0, 1),
+                Error::<Test>::Overflow
+            );
+        });
+    }
+
+    #[test]
+    fn transfer_and_renounce_ownership_work() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice hands the token over to Bob
+            assert_ok!(PRC20::transfer_ownership(
+                Origin::signed(alice.clone()),
+                0,
+                bob.clone()
+            ));
+            assert_eq!(PRC20::owner_of(0), Some(bob.clone()));
+            // Alice is no longer the owner
+            assert_noop!(
+                PRC20::mint(Origin::signed(alice.clone()), alice.clone(), 0, 1),
+                Error::<Test>::NotOwner
+            );
+            // Bob renounces the ownership, nobody can mint anymore
+            assert_ok!(PRC20::renounce_ownership(Origin::signed(bob.clone()), 0));
+            assert_eq!(PRC20::owner_of(0), None);
+            assert_noop!(
+                PRC20::mint(Origin::signed(bob.clone()), bob, 0, 1),
+                Error::<Test>::NotOwner
+            );
+        });
+    }
+
+    #[test]
+    fn frozen_account_can_not_send() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                100
+            ));
+            // only the owner can freeze
+            assert_noop!(
+                PRC20::freeze(Origin::signed(bob.clone()), 0, charlie.clone()),
+                Error::<Test>::NotOwner
+            );
+            // Alice freezes Bob
+            assert_ok!(PRC20::freeze(Origin::signed(alice.clone()), 0, bob.clone()));
+            assert!(PRC20::is_frozen(0, bob.clone()));
+            // Bob can't transfer or multi transfer
+            assert_noop!(
+                PRC20::transfer(Origin::signed(bob.clone()), charlie.clone(), 0, 10),
+                Error::<Test>::AccountFrozen
+            );
+            let transfer_vec = vec![TokenTransferDetails {
+                amount: 10,
+                to: charlie.clone(),
+            }];
+            assert_noop!(
+                PRC20::multi_transfer(Origin::signed(bob.clone()), 0, transfer_vec),
+                Error::<Test>::AccountFrozen
+            );
+            // Bob's approved spender can't move his tokens either
+            assert_ok!(PRC20::approve(
+                Origin::signed(bob.clone()),
+                charlie.clone(),
+                0,
+                10,
+                None
+            ));
+            assert_noop!(
+                PRC20::transfer_from(
+                    Origin::signed(charlie.clone()),
+                    bob.clone(),
+                    charlie.clone(),
+                    0,
+                    10
+                ),
+                Error::<Test>::AccountFrozen
+            );
+            // Bob can still receive tokens
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                100
+            ));
+            // After thawing Bob can send again
+            assert_ok!(PRC20::thaw(Origin::signed(alice), 0, bob.clone()));
+            assert_ok!(PRC20::transfer(Origin::signed(bob), charlie.clone(), 0, 10));
+            assert_eq!(PRC20::balance_of(0, charlie), 10);
+        });
+    }
+
+    #[test]
+    fn paused_token_can_not_be_swapped_or_transferred() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice pauses token 0
+            assert_ok!(PRC20::pause(Origin::signed(alice.clone()), 0));
+            assert_noop!(
+                PRC20::transfer(Origin::signed(alice.clone()), bob.clone(), 0, 10),
+                Error::<Test>::TokenPaused
+            );
+            // Bob offers token 1 for token 0
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), signed_offer.clone()),
+                Error::<Test>::TokenPaused
+            );
+            // After unpausing the swap goes through
+            assert_ok!(PRC20::unpause(Origin::signed(alice.clone()), 0));
+            assert_ok!(PRC20::swap(Origin::signed(alice), signed_offer));
+            assert_eq!(PRC20::balance_of(0, bob), 50);
+        });
+    }
+
+    #[test]
+    fn create_token_reserves_deposit() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice has TokenDeposit reserved
+            assert_eq!(NativeBalances::reserved_balance(&alice), 10);
+            assert_eq!(NativeBalances::free_balance(&alice), 990);
+            assert_eq!(PRC20::deposit_of(0), Some((alice, 10)));
+            // Charlie has no funds and can't pay the deposit
+            assert!(PRC20::create_token(
+                Origin::signed(charlie),
+                10000,
+                0,
+                TokenMetadata::default()
+            )
+            .is_err());
+            assert_eq!(PRC20::token_count(), 1);
+        });
+    }
+
+    #[test]
+    fn destroy_token_returns_deposit() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // the supply has to be burned first
+            assert_noop!(
+                PRC20::destroy_token(Origin::signed(alice.clone()), 0),
+                Error::<Test>::SupplyNotBurned
+            );
+            // allowances and frozen accounts are left behind
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                10,
+                Some(100)
+            ));
+            assert_ok!(PRC20::approve(
+                Origin::signed(bob.clone()),
+                alice.clone(),
+                0,
+                20,
+                None
+            ));
+            assert_ok!(PRC20::freeze(Origin::signed(alice.clone()), 0, bob.clone()));
+            assert_eq!(PRC20::allowance_owners(0, alice.clone()), 1);
+            assert_ok!(PRC20::burn(Origin::signed(alice.clone()), 0, 10000));
+            // only the owner can destroy the token
+            assert_noop!(
+                PRC20::destroy_token(Origin::signed(bob.clone()), 0),
+                Error::<Test>::NotOwner
+            );
+            assert_ok!(PRC20::destroy_token(Origin::signed(alice.clone()), 0));
+            // they are removed with the token
+            assert!(!<Allowance<Test>>::contains_key(
+                (0, alice.clone()),
+                bob.clone()
+            ));
+            assert!(!<Allowance<Test>>::contains_key(
+                (0, bob.clone()),
+                alice.clone()
+            ));
+            assert_eq!(
+                PRC20::allowance_expiry((0, alice.clone()), bob.clone()),
+                None
+            );
+            assert!(!<AllowanceOwners<Test>>::contains_key(0, alice.clone()));
+            assert!(!PRC20::is_frozen(0, bob));
+            // the deposit is returned and the token is gone
+            assert_eq!(NativeBalances::reserved_balance(&alice), 0);
+            assert_eq!(NativeBalances::free_balance(&alice), 1000);
+            assert_eq!(PRC20::owner_of(0), None);
+            assert_eq!(PRC20::deposit_of(0), None);
+        });
+    }
+
+    #[test]
+    fn zero_balances_are_reaped() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert!(<Balances<Test>>::contains_key(0, alice.clone()));
+            // Alice sends her whole balance to Bob
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                10000
+            ));
+            // her balance is removed from storage
+            assert!(!<Balances<Test>>::contains_key(0, alice.clone()));
+            assert_eq!(PRC20::balance_of(0, bob), 10000);
+            // without a minimum balance, keep alive only protects from zero
+            assert_noop!(
+                PRC20::transfer_keep_alive(Origin::signed(alice.clone()), alice, 0, 0),
+                Error::<Test>::KeepAlive
+            );
+        });
+    }
+
+    #[test]
+    fn min_balance_is_enforced() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            // Create Token with a minimum balance of 100
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                100,
+                TokenMetadata::default()
+            ));
+            // Bob can't receive less than the minimum balance
+            assert_noop!(
+                PRC20::transfer(Origin::signed(alice.clone()), bob.clone(), 0, 50),
+                Error::<Test>::BelowMinimum
+            );
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                150
+            ));
+            // keep alive refuses to leave Bob with 50
+            assert_noop!(
+                PRC20::transfer_keep_alive(Origin::signed(bob.clone()), charlie.clone(), 0, 100),
+                Error::<Test>::KeepAlive
+            );
+            // allow death reaps Bob and burns his 50 dust
+            assert_ok!(PRC20::transfer(
+                Origin::signed(bob.clone()),
+                charlie.clone(),
+                0,
+                100
+            ));
+            assert!(!<Balances<Test>>::contains_key(0, bob));
+            assert_eq!(PRC20::balance_of(0, charlie), 100);
+            assert_eq!(PRC20::total_supply(0), 9950);
+        });
+    }
+
+    #[test]
+    fn increase_and_decrease_allowance_work() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::increase_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                100
+            ));
+            assert_ok!(PRC20::increase_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                50
+            ));
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 150);
+            assert_ok!(PRC20::decrease_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                120
+            ));
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 30);
+            // can't decrease below zero
+            assert_noop!(
+                PRC20::decrease_allowance(Origin::signed(alice.clone()), bob.clone(), 0, 31),
+                Error::<Test>::UnderFlow
+            );
+            // decreasing to zero removes the allowance
+            assert_ok!(PRC20::decrease_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                30
+            ));
+            assert!(!<Allowance<Test>>::contains_key((0, alice), bob));
+        });
+    }
+
+    #[test]
+    fn expired_allowance_can_not_be_used() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice approves Bob until block 5
+            System::set_block_number(1);
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                100,
+                Some(5)
+            ));
+            assert_ok!(PRC20::transfer_from(
+                Origin::signed(bob.clone()),
+                alice.clone(),
+                bob.clone(),
+                0,
+                10
+            ));
+            // the allowance can't be used after block 5
+            System::set_block_number(6);
+            assert_noop!(
+                PRC20::transfer_from(
+                    Origin::signed(bob.clone()),
+                    alice.clone(),
+                    bob.clone(),
+                    0,
+                    10
+                ),
+                Error::<Test>::AllowanceExpired
+            );
+            assert_eq!(PRC20::balance_of(0, bob), 10);
+        });
+    }
+
+    #[test]
+    fn allowance_changes_keep_the_expiry() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice approves Bob until block 5
+            System::set_block_number(1);
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                100,
+                Some(5)
+            ));
+            assert_ok!(PRC20::increase_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                50
+            ));
+            assert_ok!(PRC20::decrease_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                20
+            ));
+            // the expiry set by approve is kept
+            assert_eq!(
+                PRC20::allowance_expiry((0, alice.clone()), bob.clone()),
+                Some(5)
+            );
+            // so increasing an expired allowance does not make it usable
+            System::set_block_number(6);
+            assert_ok!(PRC20::increase_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                10
+            ));
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 140);
+            assert_eq!(PRC20::usable_allowance(0, alice.clone(), bob.clone()), 0);
+            assert_noop!(
+                PRC20::transfer_from(
+                    Origin::signed(bob.clone()),
+                    alice.clone(),
+                    bob.clone(),
+                    0,
+                    10
+                ),
+                Error::<Test>::AllowanceExpired
+            );
+            // the expiry goes away with the allowance
+            assert_ok!(PRC20::decrease_allowance(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                140
+            ));
+            assert_eq!(PRC20::allowance_expiry((0, alice), bob), None);
+        });
+    }
+
+    #[test]
+    fn unlimited_allowance_is_not_decremented() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::approve(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                u128::max_value(),
+                None
+            ));
+            assert_ok!(PRC20::transfer_from(
+                Origin::signed(bob.clone()),
+                alice.clone(),
+                bob.clone(),
+                0,
+                1000
+            ));
+            assert_eq!(
+                PRC20::allowance_of((0, alice), bob.clone()),
+                u128::max_value()
+            );
+            assert_eq!(PRC20::balance_of(0, bob), 1000);
+        });
+    }
+
+    #[test]
+    fn permit_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob signs a permit letting Charlie spend 100 of his tokens
+            let permit = Permit {
+                token: 0,
+                spender: charlie.clone(),
+                value: 100,
+                nonce: 0,
+                deadline: 10,
+            };
+            let signed_permit = SignedPermit {
+                permit: permit.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(PERMIT_TAG, &permit)),
+                ),
+            };
+            // Alice submits it for Bob
+            assert_ok!(PRC20::permit(
+                Origin::signed(alice.clone()),
+                signed_permit.clone()
+            ));
+            assert_eq!(PRC20::allowance_of((0, bob.clone()), charlie), 100);
+            assert_eq!(PRC20::permit_nonce(&bob), 1);
+            // the permit can't be replayed
+            assert_noop!(
+                PRC20::permit(Origin::signed(alice), signed_permit),
+                Error::<Test>::IncorrectNonce
+            );
+        });
+    }
+
+    #[test]
+    fn permit_fails_after_deadline_or_wrong_signature() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            let bob_keyring = AccountKeyring::Bob;
+            let permit = Permit {
+                token: 0,
+                spender: charlie.clone(),
+                value: 100,
+                nonce: 0,
+                deadline: 10,
+            };
+            // Bob signs, but the permit claims Alice as the signer
+            let signed_permit = SignedPermit {
+                permit: permit.clone(),
+                signer: alice.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(PERMIT_TAG, &permit)),
+                ),
+            };
+            assert_noop!(
+                PRC20::permit(Origin::signed(charlie.clone()), signed_permit),
+                Error::<Test>::InvalidSignature
+            );
+            // a correctly signed permit past its deadline
+            System::set_block_number(11);
+            let signed_permit = SignedPermit {
+                permit: permit.clone(),
+                signer: bob,
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(PERMIT_TAG, &permit)),
+                ),
+            };
+            assert_noop!(
+                PRC20::permit(Origin::signed(charlie), signed_permit),
+                Error::<Test>::PermitExpired
+            );
+        });
+    }
+
+    #[test]
+    fn multi_transfer_all_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            let transfer_vec = vec![
+                TokenTransferDetails {
+                    amount: 5,
+                    to: bob.clone(),
+                },
+                TokenTransferDetails {
+                    amount: 5,
+                    to: charlie.clone(),
+                },
+            ];
+            assert_ok!(PRC20::multi_transfer_all(
+                Origin::signed(alice.clone()),
+                0,
+                transfer_vec
+            ));
+            assert_eq!(PRC20::balance_of(0, bob), 5);
+            assert_eq!(PRC20::balance_of(0, charlie), 5);
+            assert_eq!(PRC20::balance_of(0, alice), 9990);
+        });
+    }
+
+    #[test]
+    fn multi_transfer_all_fails_as_a_whole() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            // Create Token with a minimum balance of 10
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                10,
+                TokenMetadata::default()
+            ));
+            // the total is more than Alice owns
+            let transfer_vec = vec![
+                TokenTransferDetails {
+                    amount: 10000,
+                    to: bob.clone(),
+                },
+                TokenTransferDetails {
+                    amount: 5,
+                    to: charlie.clone(),
+                },
+            ];
+            assert_noop!(
+                PRC20::multi_transfer_all(Origin::signed(alice.clone()), 0, transfer_vec),
+                Error::<Test>::InsufficientBalance
+            );
+            // the second transfer is below the minimum balance,
+            // so the first one is reverted too
+            System::set_block_number(1);
+            let transfer_vec = vec![
+                TokenTransferDetails {
+                    amount: 100,
+                    to: bob.clone(),
+                },
+                TokenTransferDetails {
+                    amount: 5,
+                    to: charlie.clone(),
+                },
+            ];
+            assert_eq!(
+                PRC20::multi_transfer_all(Origin::signed(alice.clone()), 0, transfer_vec),
+                Err(Error::<Test>::BelowMinimum.into())
+            );
+            assert_eq!(PRC20::balance_of(0, bob), 0);
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 10000);
+            // and the index of the failing transfer is reported
+            let events = System::events();
+            assert_eq!(events.len(), 1);
+            assert_eq!(
+                events[0].event,
+                Event::prc20(RawEvent::BatchTransferFailed(0, alice, 1))
+            );
+        });
+    }
+
+    #[test]
+    fn swap_partial_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                charlie.clone(),
+                0,
+                100
+            ));
+            // Bob offers 100 token 1 for 33 token 0, partially fillable
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 33,
+                nonce: 0,
+                partial_fill: true,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            let offer_hash = PRC20::offer_hash(&bob, &offer);
+            // Alice can't take nothing
+            assert_noop!(
+                PRC20::swap_partial(Origin::signed(alice.clone()), signed_offer.clone(), 0),
+                Error::<Test>::ZeroOffer
+            );
+            // Alice takes 50, paying 16.5 rounded up to 17
+            assert_ok!(PRC20::swap_partial(
+                Origin::signed(alice.clone()),
+                signed_offer.clone(),
+                50
+            ));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 50);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 17);
+            assert_eq!(PRC20::offer_filled(offer_hash), Some(50));
+            // Charlie can't take more than the remaining 50
+            assert_noop!(
+                PRC20::swap_partial(Origin::signed(charlie.clone()), signed_offer.clone(), 51),
+                Error::<Test>::FillExceedsOffer
+            );
+            // Charlie takes the rest, paying the remaining 16
+            assert_ok!(PRC20::swap(
+                Origin::signed(charlie.clone()),
+                signed_offer.clone()
+            ));
+            assert_eq!(PRC20::balance_of(1, charlie.clone()), 50);
+            assert_eq!(PRC20::balance_of(0, charlie), 84);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 33);
+            assert_eq!(PRC20::offer_filled(offer_hash), Some(100));
+            // the completed offer can't be filled again
+            assert_noop!(
+                PRC20::swap_partial(Origin::signed(alice), signed_offer, 10),
+                Error::<Test>::OfferAlreadyFilled
+            );
+        });
+    }
+
+    #[test]
+    fn requested_for_rounds_up_only_a_remainder() {
+        ExtBuilder::build().execute_with(|| {
+            let max = u128::max_value();
+            // the products below don't fit in 128 bits
+            assert_eq!(PRC20::requested_for(3, max, 2), Ok(max / 3 * 2));
+            assert_eq!(
+                PRC20::requested_for(1 << 100, 1 << 100, 1 << 64),
+                Ok(1 << 64)
+            );
+            assert_eq!(PRC20::requested_for(7, max, 2), Ok(max / 7 * 2 + 1));
+            // nor do they here
+            assert_eq!(PRC20::requested_for(100, 33, 50), Ok(17));
+            assert_eq!(PRC20::requested_for(100, 33, 100), Ok(33));
+            assert_eq!(PRC20::requested_for(100, 50, 40), Ok(20));
+        });
+    }
+
+    #[test]
+    fn swap_fails_for_zero_offer() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob offers nothing for 50 token 0
+            let offer = Offer {
+                offer_asset: Asset::Token(0),
+                offer_amount: 0,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice), signed_offer),
+                Error::<Test>::ZeroOffer
+            );
+            assert_eq!(PRC20::offer_filled(PRC20::offer_hash(&bob, &offer)), None);
+        });
+    }
+
+    #[test]
+    fn swap_partial_fails_for_full_offer() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob,
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            assert_noop!(
+                PRC20::swap_partial(Origin::signed(alice), signed_offer, 50),
+                Error::<Test>::PartialFillNotAllowed
+            );
+        });
+    }
+
+    #[test]
+    fn cancel_offer_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob hands out a partial offer
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: true,
+                expiry: 10,
+                taker: None,
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            assert_ok!(PRC20::swap_partial(
+                Origin::signed(alice.clone()),
+                signed_offer.clone(),
+                40
+            ));
+            // Bob cancels the rest of it
+            assert_ok!(PRC20::cancel_offer(
+                Origin::signed(bob.clone()),
+                offer.clone()
+            ));
+            let offer_hash = PRC20::offer_hash(&bob, &offer);
+            assert!(PRC20::is_cancelled(offer_hash));
+            assert_noop!(
+                PRC20::cancel_offer(Origin::signed(bob.clone()), offer),
+                Error::<Test>::CancelledOffer
+            );
+            // the remaining part can't be swapped anymore
+            assert_noop!(
+                PRC20::swap_partial(Origin::signed(alice.clone()), signed_offer, 10),
+                Error::<Test>::CancelledOffer
+            );
+            assert_eq!(PRC20::balance_of(1, alice), 40);
+            // the cancellation is forgotten once the offer has expired
+            PRC20::on_initialize(11);
+            assert!(!PRC20::is_cancelled(offer_hash));
+        });
+    }
+
+    #[test]
+    fn expired_offers_are_forgotten_in_chunks() {
+        ExtBuilder::build().execute_with(|| {
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let offer = |nonce: u128, expiry: u64| Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce,
+                partial_fill: false,
+                expiry,
+                taker: None,
+                relayer_fee: None,
+            };
+            // any number of offers can expire at the same block
+            for nonce in 0..4 {
+                assert_ok!(PRC20::cancel_offer(
+                    Origin::signed(bob.clone()),
+                    offer(nonce, 10)
+                ));
+            }
+            assert_ok!(PRC20::cancel_offer(
+                Origin::signed(bob.clone()),
+                offer(4, 11)
+            ));
+            // at most 3 are forgotten in a block, the last one is left
+            PRC20::on_initialize(11);
+            assert_eq!(PRC20::offer_expiries(11).len(), 1);
+            assert_eq!(PRC20::expiry_cursor(), Some(11));
+            assert!(!PRC20::is_cancelled(PRC20::offer_hash(&bob, &offer(0, 10))));
+            assert!(PRC20::is_cancelled(PRC20::offer_hash(&bob, &offer(3, 10))));
+            // and forgotten in the next block, with the offer expiring at 11
+            PRC20::on_initialize(12);
+            assert!(PRC20::offer_expiries(11).is_empty());
+            assert!(PRC20::offer_expiries(12).is_empty());
+            assert_eq!(PRC20::expiry_cursor(), Some(13));
+            assert!(!PRC20::is_cancelled(PRC20::offer_hash(&bob, &offer(3, 10))));
+            assert!(!PRC20::is_cancelled(PRC20::offer_hash(&bob, &offer(4, 11))));
+        });
+    }
+
+    #[test]
+    fn swap_token_for_native_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            // Bob creates token 0, reserving 10 of his native funds
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob sells 100 token 0 for 50 native
+            let sign = |requested_amount: u128| {
+                let offer = Offer {
+                    offer_asset: Asset::Token(0),
+                    offer_amount: 100,
+                    requested_asset: Asset::Native,
+                    requested_amount,
+                    nonce: 0,
+                    partial_fill: false,
+                    expiry: 10,
+                    taker: None,
+                    relayer_fee: None,
+                };
+                SignedOffer {
+                    offer: offer.clone(),
+                    signer: bob.clone(),
+                    signature: Signature::from(
+                        bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                    ),
+                }
+            };
+            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), sign(50)));
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 100);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 9900);
+            assert_eq!(NativeBalances::free_balance(&alice), 950);
+            assert_eq!(NativeBalances::free_balance(&bob), 1040);
+            // Alice can't pay more native funds than she has
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), sign(2000)),
+                Error::<Test>::InsufficientBalance
+            );
+            // nor empty her account
+            assert!(PRC20::swap(Origin::signed(alice.clone()), sign(950)).is_err());
+            assert_eq!(NativeBalances::free_balance(&alice), 950);
+            assert_eq!(PRC20::balance_of(0, alice), 100);
+        });
+    }
+
+    #[test]
+    fn signatures_are_bound_to_chain_and_message_type() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            let sign = |nonce: u128, tag: Option<&[u8]>| {
+                let offer = Offer {
+                    offer_asset: Asset::Token(1),
+                    offer_amount: 100,
+                    requested_asset: Asset::Token(0),
+                    requested_amount: 50,
+                    nonce,
+                    partial_fill: false,
+                    expiry: 10,
+                    taker: None,
+                    relayer_fee: None,
+                };
+                let payload = match tag {
+                    Some(tag) => PRC20::signing_payload(tag, &offer),
+                    None => offer.encode(),
+                };
+                SignedOffer {
+                    offer: offer.clone(),
+                    signer: bob.clone(),
+                    signature: Signature::from(bob_keyring.sign(&payload)),
+                }
+            };
+            // a signature of another message type is invalid
+            let other_offer = sign(0, Some(PERMIT_TAG));
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), other_offer),
+                Error::<Test>::InvalidSignature
+            );
+            // nor is a signature over the bare offer
+            assert_noop!(
+                PRC20::swap(Origin::signed(alice.clone()), sign(1, None)),
+                Error::<Test>::InvalidSignature
+            );
+            // or over the bare permit
+            let permit = Permit {
+                token: 1,
+                spender: alice.clone(),
+                value: 100,
+                nonce: 0,
+                deadline: 10,
+            };
+            let legacy_permit = SignedPermit {
+                permit: permit.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(bob_keyring.sign(&permit.encode())),
+            };
+            assert_noop!(
+                PRC20::permit(Origin::signed(alice.clone()), legacy_permit),
+                Error::<Test>::InvalidSignature
+            );
+            assert_ok!(PRC20::swap(
+                Origin::signed(alice.clone()),
+                sign(2, Some(OFFER_TAG))
+            ));
+            assert_eq!(PRC20::balance_of(1, alice), 100);
+        });
+    }
+
+    #[test]
+    fn swap_restricted_to_taker() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Charlie has some of token 0 to pay with as well
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                charlie.clone(),
+                0,
+                1000
+            ));
+            // Bob creates an offer only Alice can take
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: false,
+                expiry: 10,
+                taker: Some(alice.clone()),
+                relayer_fee: None,
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            // Charlie can't front-run Alice
+            assert_noop!(
+                PRC20::swap(Origin::signed(charlie.clone()), signed_offer.clone()),
+                Error::<Test>::NotOfferTaker
+            );
+            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), signed_offer));
+            assert_eq!(PRC20::balance_of(1, alice), 100);
+            assert_eq!(PRC20::balance_of(1, charlie), 0);
+        });
+    }
+
+    #[test]
+    fn order_book_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let escrow = PRC20::escrow_account();
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob sells 100 token 1 for 50 token 0, then 100 more at a lower price
+            assert_ok!(PRC20::place_order(
+                Origin::signed(bob.clone()),
+                1,
+                100,
+                0,
+                50
+            ));
+            assert_ok!(PRC20::place_order(
+                Origin::signed(bob.clone()),
+                1,
+                100,
+                0,
+                40
+            ));
+            assert_eq!(PRC20::balance_of(1, bob.clone()), 9800);
+            assert_eq!(PRC20::balance_of(1, escrow.clone()), 200);
+            let (low, high) = (PRICE_UNIT * 2 / 5, PRICE_UNIT / 2);
+            assert_eq!(PRC20::price_levels((1, 0)), vec![low, high]);
+            assert_eq!(PRC20::orders_at((1, 0, high)), vec![0]);
+            // Alice fills part of the first order
+            assert_ok!(PRC20::fill_order(Origin::signed(alice.clone()), 0, 30));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 30);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 15);
+            assert_eq!(PRC20::order(0).unwrap().filled, 30);
+            assert_noop!(
+                PRC20::fill_order(Origin::signed(alice.clone()), 0, 71),
+                Error::<Test>::FillExceedsOffer
+            );
+            // then the rest of it, which removes it from the book
+            assert_ok!(PRC20::fill_order(Origin::signed(alice.clone()), 0, 70));
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 50);
+            assert_eq!(PRC20::order(0), None);
+            assert_eq!(PRC20::price_levels((1, 0)), vec![low]);
+            // only Bob can cancel his other order, which returns his tokens
+            assert_noop!(
+                PRC20::cancel_order(Origin::signed(alice.clone()), 1),
+                Error::<Test>::NotOrderMaker
+            );
+            assert_ok!(PRC20::cancel_order(Origin::signed(bob.clone()), 1));
+            assert_eq!(PRC20::balance_of(1, bob), 9900);
+            assert_eq!(PRC20::balance_of(1, escrow), 0);
+            assert!(PRC20::price_levels((1, 0)).is_empty());
+            assert_noop!(
+                PRC20::fill_order(Origin::signed(alice), 1, 10),
+                Error::<Test>::UnknownOrder
+            );
+        });
+    }
+
+    #[test]
+    fn order_book_checks_the_maker() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            // token 0 has a minimum balance of 20
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                20,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob can't ask for less than the minimum balance of token 0
+            assert_noop!(
+                PRC20::place_order(Origin::signed(bob.clone()), 1, 100, 0, 10),
+                Error::<Test>::BelowMinimum
+            );
+            // any number of orders rest at a price and at any number of prices
+            for requested_amount in vec![50, 50, 50, 40, 30, 60] {
+                assert_ok!(PRC20::place_order(
+                    Origin::signed(bob.clone()),
+                    1,
+                    100,
+                    0,
+                    requested_amount
+                ));
+            }
+            let price = PRICE_UNIT / 2;
+            assert_eq!(PRC20::orders_at((1, 0, price)), vec![0, 1, 2]);
+            assert_eq!(PRC20::orders_at_price((1, 0), price), 3);
+            assert_eq!(PRC20::price_levels((1, 0)).len(), 4);
+            // an order of a frozen maker can't be filled
+            assert_ok!(PRC20::freeze(Origin::signed(bob.clone()), 1, bob.clone()));
+            assert_noop!(
+                PRC20::fill_order(Origin::signed(alice.clone()), 0, 100),
+                Error::<Test>::AccountFrozen
+            );
+        });
+    }
+
+    #[test]
+    fn settle_batch_works() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob offers 100 token 1 for 50 token 0 in each offer
+            let sign = |nonce: u128, partial_fill: bool| {
+                let offer = Offer {
+                    offer_asset: Asset::Token(1),
+                    offer_amount: 100,
+                    requested_asset: Asset::Token(0),
+                    requested_amount: 50,
+                    nonce,
+                    partial_fill,
+                    expiry: 10,
+                    taker: None,
+                    relayer_fee: None,
+                };
+                SignedOffer {
+                    offer: offer.clone(),
+                    signer: bob.clone(),
+                    signature: Signature::from(
+                        bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                    ),
+                }
+            };
+            let mut forged = sign(2, false);
+            forged.offer.offer_amount = 1;
+            // the forged offer fails, so nothing is settled
+            System::set_block_number(1);
+            assert_eq!(
+                PRC20::settle_batch(
+                    Origin::signed(alice.clone()),
+                    vec![(sign(0, false), None), (forged.clone(), None)],
+                    true
+                ),
+                Err(Error::<Test>::InvalidSignature.into())
+            );
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 0);
+            // and the index of the failing settlement is reported
+            let events = System::events();
+            assert_eq!(events.len(), 1);
+            assert_eq!(
+                events[0].event,
+                Event::prc20(RawEvent::BatchSettlementFailed(alice.clone(), 1))
+            );
+            // best-effort settles the valid offers only
+            assert_ok!(PRC20::settle_batch(
+                Origin::signed(alice.clone()),
+                vec![
+                    (sign(0, false), None),
+                    (forged, None),
+                    (sign(1, true), Some(40))
+                ],
+                false
+            ));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 140);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 70);
+            // batches are bounded
+            let batch = (0..11).map(|nonce| (sign(nonce, false), None)).collect();
+            assert_noop!(
+                PRC20::settle_batch(Origin::signed(alice), batch, false),
+                Error::<Test>::LimitExceeded
+            );
+        });
+    }
+
+    #[test]
+    fn swap_pays_relayer_fee() {
+        ExtBuilder::build().execute_with(|| {
+            let alice = AccountId::from(AccountKeyring::Alice);
+            let bob = AccountId::from(AccountKeyring::Bob);
+            let bob_keyring = AccountKeyring::Bob;
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_ok!(PRC20::create_token(
+                Origin::signed(bob.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Bob pays a fee of 10 token 1 to whoever takes his offer
+            let offer = Offer {
+                offer_asset: Asset::Token(1),
+                offer_amount: 100,
+                requested_asset: Asset::Token(0),
+                requested_amount: 50,
+                nonce: 0,
+                partial_fill: true,
+                expiry: 10,
+                taker: None,
+                relayer_fee: Some(RelayerFee {
+                    asset: Asset::Token(1),
+                    amount: 10,
+                }),
+            };
+            let signed_offer = SignedOffer {
+                offer: offer.clone(),
+                signer: bob.clone(),
+                signature: Signature::from(
+                    bob_keyring.sign(&PRC20::signing_payload(OFFER_TAG, &offer)),
+                ),
+            };
+            // Alice takes 35 through a batch, the fee is rounded down
+            assert_ok!(PRC20::settle_batch(
+                Origin::signed(alice.clone()),
+                vec![(signed_offer.clone(), Some(35))],
+                true
+            ));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 38);
+            // taking the rest pays the rest of the fee
+            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), signed_offer));
+            assert_eq!(PRC20::balance_of(1, alice.clone()), 110);
+            assert_eq!(PRC20::balance_of(1, bob), 9890);
+            assert_eq!(PRC20::balance_of(0, alice), 9950);
         });
     }
 
     #[test]
-    fn transfer_fails_with_no_balance() {
+    fn swap_ring_works() {
         ExtBuilder::build().execute_with(|| {
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            // Create Token
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Make sure the creator has 10000 tokens
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 10000);
-            // should fail
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            // each party owns one token
+            for who in [alice.clone(), bob.clone(), charlie.clone()].iter() {
+                assert_ok!(PRC20::create_token(
+                    Origin::signed(who.clone()),
+                    10000,
+                    0,
+                    TokenMetadata::default()
+                ));
+            }
+            // Alice gives token 0 to Bob, Bob token 1 to Charlie
+            // and Charlie token 2 to Alice
+            let leg = |from: &AccountId, to: &AccountId, id: u128, amount: u128| RingLeg {
+                from: from.clone(),
+                to: to.clone(),
+                asset: Asset::Token(id),
+                amount,
+            };
+            let trade = RingTrade {
+                legs: vec![
+                    leg(&alice, &bob, 0, 100),
+                    leg(&bob, &charlie, 1, 200),
+                    leg(&charlie, &alice, 2, 300),
+                ],
+                nonce: 0,
+                expiry: 10,
+            };
+            let payload = PRC20::signing_payload(RING_TAG, &trade);
+            let signatures: Vec<Signature> = [
+                AccountKeyring::Alice,
+                AccountKeyring::Bob,
+                AccountKeyring::Charlie,
+            ]
+            .iter()
+            .map(|keyring| Signature::from(keyring.sign(&payload)))
+            .collect();
+            // a missing signature is rejected
             assert_noop!(
-                PRC20::transfer(Origin::signed(bob), alice, 0, 10),
+                PRC20::swap_ring(
+                    Origin::signed(alice.clone()),
+                    SignedRingTrade {
+                        trade: trade.clone(),
+                        signatures: signatures[..2].to_vec(),
+                    }
+                ),
+                Error::<Test>::InvalidRing
+            );
+            // so is a signature of the wrong party
+            let mut swapped = signatures.clone();
+            swapped.swap(0, 1);
+            assert_noop!(
+                PRC20::swap_ring(
+                    Origin::signed(alice.clone()),
+                    SignedRingTrade {
+                        trade: trade.clone(),
+                        signatures: swapped,
+                    }
+                ),
+                Error::<Test>::InvalidSignature
+            );
+            // if a party can't pay nothing is settled
+            let mut too_much = trade.clone();
+            too_much.legs[2].amount = 10001;
+            let payload = PRC20::signing_payload(RING_TAG, &too_much);
+            let too_much_signatures = [
+                AccountKeyring::Alice,
+                AccountKeyring::Bob,
+                AccountKeyring::Charlie,
+            ]
+            .iter()
+            .map(|keyring| Signature::from(keyring.sign(&payload)))
+            .collect();
+            assert_noop!(
+                PRC20::swap_ring(
+                    Origin::signed(alice.clone()),
+                    SignedRingTrade {
+                        trade: too_much,
+                        signatures: too_much_signatures,
+                    }
+                ),
                 Error::<Test>::InsufficientBalance
             );
+            // the signed trade settles every leg
+            let signed_trade = SignedRingTrade { trade, signatures };
+            assert_ok!(PRC20::swap_ring(
+                Origin::signed(alice.clone()),
+                signed_trade.clone()
+            ));
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 100);
+            assert_eq!(PRC20::balance_of(1, charlie.clone()), 200);
+            assert_eq!(PRC20::balance_of(2, alice.clone()), 300);
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 9900);
+            // and can't be replayed
+            assert_noop!(
+                PRC20::swap_ring(Origin::signed(bob), signed_trade),
+                Error::<Test>::OfferAlreadyFilled
+            );
         });
     }
 
     #[test]
-    fn approve_token_works() {
+    fn htlc_works() {
         ExtBuilder::build().execute_with(|| {
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            // Make sure token count is 0
-            assert_eq!(PRC20::token_count(), 0);
-            // Create Token
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Make sure token count is now 1
-            assert_eq!(PRC20::token_count(), 1);
-            // Make sure the creator has 10000 tokens
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 10000);
-
-            // Maybe make sure initial approval is 0
-            assert_eq!(PRC20::allowance_of((0, alice.clone(), bob.clone())), 0);
-            // Approve account 1 to use 10 tokens from account 0
-            assert_ok!(PRC20::approve(
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            let hashlock = sp_io::hashing::sha2_256(b"secret");
+            // Alice locks 100 tokens for Bob until block 5
+            assert_noop!(
+                PRC20::lock_htlc(
+                    Origin::signed(alice.clone()),
+                    bob.clone(),
+                    Asset::Token(0),
+                    100,
+                    hashlock,
+                    0
+                ),
+                Error::<Test>::HtlcExpired
+            );
+            assert_ok!(PRC20::lock_htlc(
                 Origin::signed(alice.clone()),
                 bob.clone(),
+                Asset::Token(0),
+                100,
+                hashlock,
+                5
+            ));
+            assert_eq!(PRC20::balance_of(0, PRC20::escrow_account()), 100);
+            // it can't be refunded yet, nor claimed without the preimage
+            assert_noop!(
+                PRC20::refund_htlc(Origin::signed(alice.clone()), 0),
+                Error::<Test>::HtlcNotExpired
+            );
+            assert_noop!(
+                PRC20::claim_htlc(Origin::signed(bob.clone()), 0, b"guess".to_vec()),
+                Error::<Test>::InvalidPreimage
+            );
+            // nothing is released while the token is paused
+            assert_ok!(PRC20::pause(Origin::signed(alice.clone()), 0));
+            assert_noop!(
+                PRC20::claim_htlc(Origin::signed(bob.clone()), 0, b"secret".to_vec()),
+                Error::<Test>::TokenPaused
+            );
+            assert_ok!(PRC20::unpause(Origin::signed(alice.clone()), 0));
+            // anyone revealing the preimage pays Bob
+            assert_ok!(PRC20::claim_htlc(
+                Origin::signed(alice.clone()),
                 0,
-                10
+                b"secret".to_vec()
             ));
-            // Maybe make sure current approval is 10
-            assert_eq!(PRC20::allowance_of((0, alice, bob)), 10);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 100);
+            assert_eq!(PRC20::balance_of(0, PRC20::escrow_account()), 0);
+            assert_eq!(PRC20::htlc(0), None);
+            // Alice locks native currency, which Bob does not claim in time
+            assert_ok!(PRC20::lock_htlc(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                Asset::Native,
+                100,
+                hashlock,
+                5
+            ));
+            assert_eq!(NativeBalances::free_balance(&alice), 890);
+            System::set_block_number(6);
+            assert_noop!(
+                PRC20::claim_htlc(Origin::signed(bob.clone()), 1, b"secret".to_vec()),
+                Error::<Test>::HtlcExpired
+            );
+            assert_ok!(PRC20::refund_htlc(Origin::signed(bob), 1));
+            assert_eq!(NativeBalances::free_balance(&alice), 990);
+            assert_noop!(
+                PRC20::refund_htlc(Origin::signed(alice), 1),
+                Error::<Test>::UnknownHtlc
+            );
         });
     }
 
     #[test]
-    fn transfer_from_works() {
+    fn account_tokens_index_works() {
         ExtBuilder::build().execute_with(|| {
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            // Make sure token count is 0
-            assert_eq!(PRC20::token_count(), 0);
-            // Create Token
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Make sure token count is now 1
-            assert_eq!(PRC20::token_count(), 1);
-            // Make sure the creator has 10000 tokens
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 10000);
-            // Maybe make sure initial approval is 0
-            assert_eq!(PRC20::allowance_of((0, alice.clone(), bob.clone())), 0);
-            // Approve account 1 to use 10 tokens from account 0
-            assert_ok!(PRC20::approve(
+            for _ in 0..3 {
+                assert_ok!(PRC20::create_token(
+                    Origin::signed(alice.clone()),
+                    10000,
+                    0,
+                    TokenMetadata::default()
+                ));
+            }
+            assert_eq!(
+                PRC20::tokens_of(alice.clone(), 0, 10),
+                vec![(0, 10000), (1, 10000), (2, 10000)]
+            );
+            // Bob is indexed once he receives tokens, lowest id first
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                2,
+                20
+            ));
+            assert_ok!(PRC20::transfer(
                 Origin::signed(alice.clone()),
                 bob.clone(),
                 0,
                 10
             ));
-            // Maybe make sure current approval is 10
-            assert_eq!(PRC20::allowance_of((0, alice.clone(), bob.clone())), 10);
-            // Now lets use transfer_from
-            assert_ok!(PRC20::transfer_from(
+            assert_eq!(PRC20::tokens_of(bob.clone(), 0, 10), vec![(0, 10), (2, 20)]);
+            // holdings are paged
+            assert_eq!(PRC20::tokens_of(alice.clone(), 1, 1), vec![(1, 10000)]);
+            assert!(PRC20::tokens_of(alice.clone(), 3, 10).is_empty());
+            // and removed once the balance is zero
+            assert_ok!(PRC20::transfer(
                 Origin::signed(bob.clone()),
-                alice,
-                bob.clone(),
+                alice.clone(),
                 0,
                 10
             ));
-            // Make sure balance is updated correctly
-            assert_eq!(PRC20::balance_of((0, bob)), 10);
+            assert!(!PRC20::holds_token(&bob, 0));
+            assert!(PRC20::holds_token(&bob, 2));
+            assert_ok!(PRC20::transfer(Origin::signed(bob.clone()), alice, 2, 20));
+            assert!(<AccountTokens<Test>>::iter_prefix(&bob).next().is_none());
         });
     }
 
     #[test]
-    fn swap_works() {
+    fn migrate_to_v2_works() {
         ExtBuilder::build().execute_with(|| {
-            // get account id for alice and bob
-            let alice = AccountId::from(AccountKeyring::Alice);
-            let bob = AccountId::from(AccountKeyring::Bob);
-            // get account keyring for Bob
-            let bob_keyring = AccountKeyring::Bob;
-
-            // Alice creates token 0
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Bob creates token 1
-            assert_ok!(PRC20::create_token(Origin::signed(bob.clone()), 10000));
-
-            // Now bob creates an offer struct
-            let offer = Offer {
-                offer_token: 1,
-                offer_amount: 100,
-                requested_token: 0,
-                requested_amount: 50,
-                nonce: 0, // nonce is 0 cuz in tests, nonce doesn't increment
-            };
-            // bob signs this using bob_keyring to create a signed_offer
-            let signed_offer = SignedOffer {
-                offer: offer.clone(),
-                signer: bob.clone(),
-                signature: Signature::from(bob_keyring.sign(&offer.encode())),
+            use frame_support::{
+                storage::{
+                    migration::{get_storage_value, put_storage_value},
+                    IterableStorageDoubleMap,
+                },
+                Blake2_128Concat, StorageHasher,
             };
-            // make sure swap is ok
-            assert_ok!(PRC20::swap(Origin::signed(alice.clone()), signed_offer));
-            // Bob has 50 token 0
-            assert_eq!(PRC20::balance_of((0, bob.clone())), 50);
-            // Bob has 9900 token 1
-            assert_eq!(PRC20::balance_of((1, bob)), 9900);
-            // Alice has 9950 token 0
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 9950);
-            // Alice has 100 token 1
-            assert_eq!(PRC20::balance_of((1, alice)), 100);
-            // TODO: check bob nonce has increased
-            // assert_eq!( Test::check_nonce(&bob), 2);
-        });
-    }
-
-    #[test]
-    fn swap_fails_not_enough_balance() {
-        ExtBuilder::build().execute_with(|| {
-            // get account id for alice and bob
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            // get account keyring for Bob
-            let bob_keyring = AccountKeyring::Bob;
-            // Alice creates token 0
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Bob creates token 1
-            assert_ok!(PRC20::create_token(Origin::signed(bob.clone()), 10000));
-
-            // Now bob creates an offer struct
-            // (invalid since Bob owns token 1 and not 0)
-            let offer = Offer {
-                offer_token: 0,
-                offer_amount: 100,
-                requested_token: 1,
-                requested_amount: 50,
-                nonce: 0,
-            };
-            // bob signs this using bob_keyring to create a signed_offer
-            let signed_offer = SignedOffer {
-                offer: offer.clone(),
-                signer: bob.clone(),
-                signature: Signature::from(bob_keyring.sign(&offer.encode())),
-            };
-            // make sure swap fails
-            assert_noop!(
-                PRC20::swap(Origin::signed(alice.clone()), signed_offer),
-                Error::<Test>::InsufficientBalance
+            // populate the tuple keyed maps of a chain created before V2
+            let balances = vec![
+                ((0u128, alice.clone()), 900u128),
+                ((0, bob.clone()), 100),
+                ((1, bob.clone()), 50),
+            ];
+            let old_keys: Vec<Vec<u8>> = balances
+                .iter()
+                .map(|(key, balance)| {
+                    let hash = Blake2_128Concat::hash(&key.encode());
+                    put_storage_value(b"PRC20", b"Balances", &hash, *balance);
+                    hash
+                })
+                .collect();
+            let allowance_key =
+                Blake2_128Concat::hash(&(0u128, alice.clone(), bob.clone()).encode());
+            put_storage_value(b"PRC20", b"Allowance", &allowance_key, 30u128);
+            let expiry_key = Blake2_128Concat::hash(&(0u128, alice.clone(), bob.clone()).encode());
+            put_storage_value(b"PRC20", b"AllowanceExpiry", &expiry_key, 40u64);
+            let frozen_key = Blake2_128Concat::hash(&(1u128, bob.clone()).encode());
+            put_storage_value(b"PRC20", b"FrozenAccounts", &frozen_key, true);
+            // a key that does not decode is left in place
+            put_storage_value(b"PRC20", b"Balances", &[1, 2, 3], 5u128);
+            // and the index of a chain that ran the first AccountTokens layout
+            put_storage_value(b"PRC20", b"AccountTokensIndexed", &[], true);
+            put_storage_value(
+                b"PRC20",
+                b"AccountTokens",
+                &Blake2_128Concat::hash(&alice.encode()),
+                vec![0u128, 7],
             );
-            // Bob has 0 token 0
-            assert_eq!(PRC20::balance_of((0, bob.clone())), 0);
-            // Bob has 9900 token 1
-            assert_eq!(PRC20::balance_of((1, bob)), 10000);
-            // Alice has 9950 token 0
-            assert_eq!(PRC20::balance_of((0, alice.clone())), 10000);
-            // Alice has 100 token 1
-            assert_eq!(PRC20::balance_of((1, alice)), 0);
+            assert_eq!(PRC20::storage_version(), Releases::V1);
+            PRC20::on_runtime_upgrade();
+            // the balances and allowances are re-keyed and indexed
+            assert_eq!(PRC20::storage_version(), Releases::V2);
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 900);
+            assert_eq!(PRC20::balance_of(0, bob.clone()), 100);
+            assert_eq!(PRC20::balance_of(1, bob.clone()), 50);
+            assert_eq!(PRC20::allowance_of((0, alice.clone()), bob.clone()), 30);
+            assert_eq!(
+                PRC20::allowance_expiry((0, alice.clone()), bob.clone()),
+                Some(40)
+            );
+            assert_eq!(PRC20::allowance_owners(0, alice.clone()), 1);
+            assert!(PRC20::is_frozen(1, bob.clone()));
+            assert_eq!(
+                PRC20::tokens_of(bob.clone(), 0, 10),
+                vec![(0, 100), (1, 50)]
+            );
+            assert_eq!(PRC20::holder_count(0), 2);
+            let holders: Vec<_> = <Balances<Test>>::iter_prefix(0).collect();
+            assert_eq!(holders.len(), 2);
+            for key in old_keys.iter() {
+                assert_eq!(get_storage_value::<u128>(b"PRC20", b"Balances", key), None);
+            }
+            assert_eq!(
+                get_storage_value::<u128>(b"PRC20", b"Allowance", &allowance_key),
+                None
+            );
+            assert_eq!(
+                get_storage_value::<bool>(b"PRC20", b"AccountTokensIndexed", &[]),
+                None
+            );
+            assert_eq!(
+                get_storage_value::<u128>(b"PRC20", b"Balances", &[1, 2, 3]),
+                Some(5)
+            );
+            assert_eq!(PRC20::tokens_of(alice.clone(), 0, 10), vec![(0, 900)]);
+            // the migration only runs once
+            PRC20::on_runtime_upgrade();
+            assert_eq!(PRC20::balance_of(0, alice.clone()), 900);
+            assert_eq!(PRC20::tokens_of(alice.clone(), 0, 10), vec![(0, 900)]);
         });
     }
 
     #[test]
-    fn swap_fails_wrong_nonce() {
+    fn holder_count_and_top_holders_work() {
         ExtBuilder::build().execute_with(|| {
-            // get account id for alice and bob
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            // get account keyring for Bob
-            let bob_keyring = AccountKeyring::Bob;
-            // Alice creates token 0
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // Bob creates token 1
-            assert_ok!(PRC20::create_token(Origin::signed(bob.clone()), 10000));
-            // Now bob creates an offer struct with wrong nonce
-            let offer = Offer {
-                offer_token: 1,
-                offer_amount: 100,
-                requested_token: 0,
-                requested_amount: 50,
-                nonce: 2, // should be 0 (nonce doesn't increment in tests)
-            };
-            // bob signs this using bob_keyring to create a signed_offer
-            let signed_offer = SignedOffer {
-                offer: offer.clone(),
-                signer: bob.clone(),
-                signature: Signature::from(bob_keyring.sign(&offer.encode())),
-            };
-            // make sure swap is ok
-            assert_noop!(
-                PRC20::swap(Origin::signed(alice.clone()), signed_offer),
-                Error::<Test>::IncorrectNonce
+            let charlie = AccountId::from(AccountKeyring::Charlie);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            assert_eq!(PRC20::holder_count(0), 1);
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                3000
+            ));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                charlie.clone(),
+                0,
+                1000
+            ));
+            // a transfer between holders does not change the count
+            assert_ok!(PRC20::transfer(
+                Origin::signed(bob.clone()),
+                charlie.clone(),
+                0,
+                500
+            ));
+            assert_eq!(PRC20::holder_count(0), 3);
+            assert_eq!(
+                PRC20::top_holders(0, 10),
+                vec![
+                    (alice.clone(), 6000),
+                    (bob.clone(), 2500),
+                    (charlie.clone(), 1500)
+                ]
             );
-            // Bob has 10000 token 1
-            assert_eq!(PRC20::balance_of((1, bob.clone())), 10000);
-            // Alice has 100 token 1
-            assert_eq!(PRC20::balance_of((0, alice)), 10000);
+            assert_eq!(PRC20::top_holders(0, 1), vec![(alice.clone(), 6000)]);
+            // an emptied account is no longer a holder
+            assert_ok!(PRC20::transfer(
+                Origin::signed(charlie.clone()),
+                alice.clone(),
+                0,
+                1500
+            ));
+            assert_eq!(PRC20::holder_count(0), 2);
+            assert_eq!(PRC20::top_holders(0, 10).len(), 2);
+            // the order follows the balance changes
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                6000
+            ));
+            assert_eq!(
+                PRC20::top_holders(0, 10),
+                vec![(bob.clone(), 8500), (alice.clone(), 1500)]
+            );
+            assert_eq!(PRC20::holder_count(1), 0);
+            assert!(PRC20::top_holders(1, 10).is_empty());
         });
     }
 
     #[test]
-    fn multi_transfer_works() {
+    fn snapshots_work() {
         ExtBuilder::build().execute_with(|| {
-            // get account id for alice and bob
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            let charlie = AccountId::from(AccountKeyring::Charlie);
-            // Alice creates token 0
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // create first transfer details struct
-            let first_transfer = TokenTransferDetails {
-                amount: 5,
-                to: bob.clone(),
-            };
-            // create second transfer details struct
-            let second_transfer = TokenTransferDetails {
-                amount: 5,
-                to: charlie.clone(),
-            };
-            // Create a vector of these transfer details struct
-            let transfer_vec = vec![first_transfer, second_transfer];
-            // do a multi transfer from alice's account
-            assert_ok!(PRC20::multi_transfer(
+            assert_ok!(PRC20::create_token(
                 Origin::signed(alice.clone()),
+                10000,
                 0,
-                transfer_vec
+                TokenMetadata::default()
             ));
-            // Bob has 5 token 0
-            assert_eq!(PRC20::balance_of((0, bob)), 5);
-            // Charlie has 5 token 0
-            assert_eq!(PRC20::balance_of((0, charlie)), 5);
-            // Alice has 9990 token 0
-            assert_eq!(PRC20::balance_of((0, alice)), 9990);
+            // only the owner takes snapshots
+            assert_noop!(
+                PRC20::snapshot(Origin::signed(bob.clone()), 0),
+                Error::<Test>::NotOwner
+            );
+            assert_eq!(PRC20::balance_of_at(0, alice.clone(), 1), None);
+            assert_ok!(PRC20::snapshot(Origin::signed(alice.clone()), 0));
+            // nothing is written until a balance changes
+            assert_eq!(PRC20::balance_checkpoints(0, &alice), (0, 0));
+            assert_eq!(PRC20::balance_of_at(0, alice.clone(), 1), Some(10000));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                1000
+            ));
+            assert_ok!(PRC20::transfer(
+                Origin::signed(alice.clone()),
+                bob.clone(),
+                0,
+                1000
+            ));
+            assert_eq!(PRC20::balance_checkpoints(0, &alice), (1, 1));
+            assert_eq!(PRC20::balance_checkpoint(0, (alice.clone(), 0)), (1, 10000));
+            assert_ok!(PRC20::snapshot(Origin::signed(alice.clone()), 0));
+            assert_ok!(PRC20::burn(Origin::signed(alice.clone()), 0, 3000));
+            assert_ok!(PRC20::snapshot(Origin::signed(alice.clone()), 0));
+            // balances and supply at each snapshot
+            assert_eq!(PRC20::balance_of_at(0, alice.clone(), 1), Some(10000));
+            assert_eq!(PRC20::balance_of_at(0, alice.clone(), 2), Some(8000));
+            assert_eq!(PRC20::balance_of_at(0, alice.clone(), 3), Some(5000));
+            assert_eq!(PRC20::balance_of_at(0, bob.clone(), 1), Some(0));
+            assert_eq!(PRC20::balance_of_at(0, bob.clone(), 3), Some(2000));
+            assert_eq!(PRC20::total_supply_at(0, 1), Some(10000));
+            assert_eq!(PRC20::total_supply_at(0, 2), Some(10000));
+            assert_eq!(PRC20::total_supply_at(0, 3), Some(7000));
+            assert_eq!(PRC20::total_supply_at(0, 4), None);
         });
     }
 
     #[test]
-    fn multi_transfer_fails_sequentially() {
-        // this basically means if the user does not have enough
-        // balances for all transfers, the first transfers in the vec
-        // have priority and go through
+    fn balance_at_many_snapshots_works() {
         ExtBuilder::build().execute_with(|| {
-            // get account id for alice and bob
             let alice = AccountId::from(AccountKeyring::Alice);
             let bob = AccountId::from(AccountKeyring::Bob);
-            let charlie = AccountId::from(AccountKeyring::Charlie);
-            // Alice creates token 0
-            assert_ok!(PRC20::create_token(Origin::signed(alice.clone()), 10000));
-            // create first transfer details struct
-            let first_transfer = TokenTransferDetails {
-                amount: 10000,
-                to: bob.clone(),
-            };
-            // create second transfer details struct
-            let second_transfer = TokenTransferDetails {
-                amount: 5,
-                to: charlie.clone(),
-            };
-            // Create a vector of these transfer details struct
-            let transfer_vec = vec![first_transfer, second_transfer];
-
-            let _partial_transfer_result =
-                PRC20::multi_transfer(Origin::signed(alice.clone()), 0, transfer_vec);
-            // Bob has 10000 token 0
-            assert_eq!(PRC20::balance_of((0, bob)), 10000);
-            // Alice has 0 token 0
-            assert_eq!(PRC20::balance_of((0, alice)), 0);
-            // Charlie has 0 token 0 since the second transfer
-            // in the vector should fail
-            assert_eq!(PRC20::balance_of((0, charlie)), 0);
+            assert_ok!(PRC20::create_token(
+                Origin::signed(alice.clone()),
+                10000,
+                0,
+                TokenMetadata::default()
+            ));
+            // Alice sends 100 after every snapshot, and nothing
+            // after every third one
+            for snapshot_id in 1..=20u32 {
+                assert_ok!(PRC20::snapshot(Origin::signed(alice.clone()), 0));
+                if snapshot_id % 3 != 0 {
+                    assert_ok!(PRC20::transfer(
+                        Origin::signed(alice.clone()),
+                        bob.clone(),
+                        0,
+                        100
+                    ));
+                }
+            }
+            // one checkpoint per snapshot followed by a transfer
+            assert_eq!(PRC20::balance_checkpoints(0, &alice), (14, 20));
+            let mut balance = 10000;
+            for snapshot_id in 1..=20u32 {
+                assert_eq!(
+                    PRC20::balance_of_at(0, alice.clone(), snapshot_id),
+                    Some(balance)
+                );
+                assert_eq!(
+                    PRC20::balance_of_at(0, bob.clone(), snapshot_id),
+                    Some(10000 - balance)
+                );
+                if snapshot_id % 3 != 0 {
+                    balance -= 100;
+                }
+            }
+            assert_eq!(PRC20::balance_of(0, alice), balance);
         });
     }
 }
@@ -0,0 +1,32 @@
+/*
+Copyright (C) 2020 OAX Foundation Limited
+
+This program is free software: you can redistribute it and/or modify it under the
+terms of the GNU General Public License as published by the Free Software Foundation,
+either version 3 of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY
+or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with
+this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+#![cfg_attr(not(feature = "std"), no_std)]
+//! # Offline Signing
+//! The bytes signed offline for the messages of the parrot pallets
+//! (offers, permits, delegated transfers...), they bind a signature to one
+//! chain and one message type
+use codec::Encode;
+use frame_support::traits::Get;
+use sp_runtime::traits::Zero;
+use sp_std::vec::Vec;
+
+/// The bytes signed offline for a message: the message tag, the genesis
+/// hash and spec name of the chain, then the message itself
+pub fn signing_payload<T: frame_system::Trait, M: Encode>(tag: &[u8], message: &M) -> Vec<u8> {
+    let genesis_hash = <frame_system::Module<T>>::block_hash(T::BlockNumber::zero());
+    let spec_name = T::Version::get().spec_name;
+    (tag, genesis_hash, spec_name, message).encode()
+}
@@ -47,6 +47,7 @@ pub use sp_runtime::{Perbill, Permill};
 pub use timestamp::Call as TimestampCall;
 
 // OAX custom pallets
+pub use amm;
 pub use burn;
 pub use delegation;
 pub use multi_transfer;
@@ -121,10 +122,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("parrot"),
     impl_name: create_runtime_str!("parrot"),
     authoring_version: 1,
-    spec_version: 1,
+    spec_version: 2,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    transaction_version: 2,
 };
 
 pub const MILLISECS_PER_BLOCK: u64 = 6000;
@@ -343,22 +344,58 @@ impl multi_transfer::Trait for Runtime {
     type MaxTransfers = MaxTransfers;
 }
 
+// Offline signatures over the bare message (without the chain and message tag)
+// are accepted by delegation for this many blocks after the runtime upgrade
+// binding signatures to the chain
+parameter_types! {
+    pub const LegacySignatureWindow: BlockNumber = 30 * DAYS;
+}
 // Implement the delegation trait for runtime
 impl delegation::Trait for Runtime {
     type Event = Event;
     type Public = <MultiSignature as Verify>::Signer;
     type Signature = MultiSignature;
     type Currency = Balances;
+    type LegacySignatureWindow = LegacySignatureWindow;
 }
 
+// These are the maximum lengths (in bytes) of the prc20 token metadata fields
+parameter_types! {
+    pub const MaxNameLength: u32 = 64;
+    pub const MaxSymbolLength: u32 = 16;
+    pub const MaxUriLength: u32 = 256;
+    // Deposit reserved from the creator of a prc20 token
+    pub const TokenDeposit: Balance = deposit(5, 256);
+    // Max number of offers settled in a single prc20 settle_batch
+    pub const MaxBatchSize: u32 = 50;
+    // Max number of expired prc20 offers and ring trades forgotten in a single block
+    pub const MaxOffersPerBlock: u32 = 512;
+}
 //Implement the prc20 trait for runtime
 impl prc20::Trait for Runtime {
     type Event = Event;
+    type Currency = Balances;
+    type TokenDeposit = TokenDeposit;
     type TokenBalance = u128;
     type TokenId = u128;
     type Public = <MultiSignature as Verify>::Signer;
     type Signature = MultiSignature;
     type MaxTransfers = MaxTransfers;
+    type MaxNameLength = MaxNameLength;
+    type MaxSymbolLength = MaxSymbolLength;
+    type MaxUriLength = MaxUriLength;
+    type MaxBatchSize = MaxBatchSize;
+    type MaxOffersPerBlock = MaxOffersPerBlock;
+}
+
+// This is the fee taken from the input amount of every amm swap (0.3%)
+parameter_types! {
+    pub const SwapFee: Permill = Permill::from_parts(3_000);
+}
+// Implement the amm trait for runtime
+impl amm::Trait for Runtime {
+    type Event = Event;
+    type SwapFee = SwapFee;
 }
 
 // This is a configurable constant, that sets the number of blocks to
@@ -391,9 +428,10 @@ construct_runtime!(
         Authorship: pallet_authorship::{Module, Call, Storage},
         // OAX custom pallets
         MultiTransfer: multi_transfer::{Module, Call, Event<T>},
-        PRC20: prc20::{Module, Call, Storage, Event<T>},
-        Delegation: delegation::{Module, Call, Event<T>},
+        PRC20: prc20::{Module, Call, Storage, Config, Event<T>},
+        Delegation: delegation::{Module, Call, Storage, Config, Event<T>},
         Burner: burn::{Module, Call, Storage, Event<T>, Config<T>},
+        Amm: amm::{Module, Call, Storage, Event<T>},
     }
 );
 
@@ -511,6 +549,66 @@ impl_runtime_apis! {
         }
     }
 
+    impl prc20_runtime_api::Prc20Api<Block, AccountId, u128, u128, prc20::TokenMetadata> for Runtime {
+        fn balance_of(token: u128, who: AccountId) -> u128 {
+            PRC20::balance_of(token, who)
+        }
+
+        fn allowance_of(token: u128, owner: AccountId, spender: AccountId) -> u128 {
+            PRC20::usable_allowance(token, owner, spender)
+        }
+
+        fn total_supply(token: u128) -> u128 {
+            PRC20::total_supply(token)
+        }
+
+        fn token_count() -> u128 {
+            PRC20::token_count()
+        }
+
+        fn token_metadata(token: u128) -> Option<prc20::TokenMetadata> {
+            PRC20::token_metadata(token)
+        }
+
+        fn tokens_of(who: AccountId, start: u32, limit: u32) -> Vec<(u128, u128)> {
+            PRC20::tokens_of(who, start, limit)
+        }
+
+        fn holder_count(token: u128) -> u32 {
+            PRC20::holder_count(token)
+        }
+
+        fn top_holders(token: u128, count: u32) -> Vec<(AccountId, u128)> {
+            PRC20::top_holders(token, count)
+        }
+
+        fn current_snapshot(token: u128) -> u32 {
+            PRC20::current_snapshot(token)
+        }
+
+        fn balance_of_at(token: u128, who: AccountId, snapshot: u32) -> Option<u128> {
+            PRC20::balance_of_at(token, who, snapshot)
+        }
+
+        fn total_supply_at(token: u128, snapshot: u32) -> Option<u128> {
+            PRC20::total_supply_at(token, snapshot)
+        }
+    }
+
+    impl amm_runtime_api::AmmApi<Block, u128, u128> for Runtime {
+        fn quote_exact_in(token_in: u128, token_out: u128, amount_in: u128) -> Option<u128> {
+            Amm::quote_exact_in(token_in, token_out, amount_in)
+        }
+
+        fn quote_exact_out(token_in: u128, token_out: u128, amount_out: u128) -> Option<u128> {
+            Amm::quote_exact_out(token_in, token_out, amount_out)
+        }
+
+        fn reserves(token_a: u128, token_b: u128) -> Option<(u128, u128)> {
+            Amm::reserves(token_a, token_b)
+        }
+    }
+
     impl fg_primitives::GrandpaApi<Block> for Runtime {
         fn grandpa_authorities() -> GrandpaAuthorityList {
             Grandpa::grandpa_authorities()